@@ -1,11 +1,12 @@
-use rocust_lib::{test::user::User, EndPoint, Master, Runnable, Test, Worker, traits::HasResults};
+use rocust_lib::{EndPoint, Master, Runnable, Test, Worker, traits::HasResults};
 use std::{process::exit, time::Duration};
 
+#[allow(unreachable_code)]
 #[tokio::main(flavor = "multi_thread", worker_threads = 1000)]
 async fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
 
-    let mut test = Test::new(
+    let test = Test::new(
         String::from("test1"),
         21,
         Some(5),
@@ -55,7 +56,7 @@ async fn main() {
         false,
         false,
     );
-    let master_c = master.clone();
+    let _master_c = master.clone();
 
     // tokio::spawn(async move {
     //     tokio::time::sleep(Duration::from_secs(15)).await;
@@ -92,7 +93,7 @@ async fn main() {
     //println!("Master finished: {:?}", master);
     // //println!("{:?}", master);
     //tokio::time::sleep(Duration::from_secs(60)).await;
-    exit(0);
+    exit(master.get_status().get_exit_code());
     //tokio::time::sleep(Duration::from_secs(60)).await;
 
     // let test_handler = test.clone();
@@ -161,5 +162,5 @@ async fn main() {
     // let u: Test = serde_json::from_str(&j).unwrap();
     // println!("############################################################");
     // println!("after: {:?}", u);
-    exit(0);
+    exit(test.get_status().get_exit_code());
 }
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt};
+
+/// Starts iterations at a fixed rate, whether the responses are fast or slow (open model).
+/// An iteration is one request, or one scenario run if the test has scenarios, sent without thinking.
+/// Every iteration is run by an idle user of the pool. If all users are busy, a new user is added up to the maximum,
+/// after that the iteration is dropped and counted.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ArrivalRate {
+    pub rate: f64,                //ITERATIONS PER SECOND
+    pub pre_allocated_users: u32, //USERS CREATED BEFORE THE FIRST ITERATION
+    pub max_users: u32,
+}
+
+impl ArrivalRate {
+    pub fn new(rate: f64, pre_allocated_users: u32, max_users: u32) -> ArrivalRate {
+        ArrivalRate {
+            rate,
+            pre_allocated_users,
+            max_users,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !(self.rate > 0.0 && self.rate.is_finite()) {
+            return Err(format!("invalid arrival rate [{}]", self.rate).into());
+        }
+        if self.max_users == 0 {
+            return Err("the arrival rate has a maximum of 0 users".into());
+        }
+        if self.pre_allocated_users > self.max_users {
+            return Err(format!(
+                "the arrival rate has {} pre-allocated users for a maximum of {} users",
+                self.pre_allocated_users, self.max_users
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    // the share of the rate and of the users that belongs to the users from offset to offset + user_count.
+    // used by the master, so the workers together start iterations at the rate
+    pub fn partition(&self, offset: u32, user_count: u32, total_user_count: u32) -> ArrivalRate {
+        if total_user_count == 0 {
+            return self.clone();
+        }
+        let share = |users: u32| {
+            let end = users as u64 * (offset + user_count) as u64 / total_user_count as u64;
+            let start = users as u64 * offset as u64 / total_user_count as u64;
+            (end - start) as u32
+        };
+        ArrivalRate {
+            rate: self.rate * user_count as f64 / total_user_count as f64,
+            pre_allocated_users: share(self.pre_allocated_users),
+            max_users: share(self.max_users),
+        }
+    }
+}
+
+impl fmt::Display for ArrivalRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} iterations per second | Users [{}-{}]",
+            self.rate, self.pre_allocated_users, self.max_users
+        )
+    }
+}
@@ -0,0 +1,506 @@
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+pub const DEFAULT_REFRESH_BEFORE: u64 = 30;
+pub const TOKEN_RETRY_MIN_BACKOFF: u64 = 500; //MILLISECONDS
+pub const TOKEN_RETRY_MAX_BACKOFF: u64 = 30000; //MILLISECONDS
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum OAuth2Grant {
+    ClientCredentials,
+    Password { username: String, password: String },
+}
+
+fn default_refresh_before() -> u64 {
+    DEFAULT_REFRESH_BEFORE
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OAuth2Config {
+    pub token_url: String, //RELATIVE TO THE HOST OF THE TEST IF IT STARTS WITH A SLASH
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub grant: OAuth2Grant,
+    pub scope: Option<String>,
+    #[serde(default = "default_refresh_before")]
+    pub refresh_before: u64, //SECONDS BEFORE THE EXPIRY OF A TOKEN, WHEN IT IS REFRESHED. AT MOST HALF THE LIFETIME OF THE TOKEN
+    #[serde(default)]
+    pub shared: bool, //ONE TOKEN FOR ALL USERS INSTEAD OF ONE PER USER
+}
+
+impl OAuth2Config {
+    pub fn new(token_url: String, client_id: String, grant: OAuth2Grant) -> OAuth2Config {
+        OAuth2Config {
+            token_url,
+            client_id,
+            client_secret: None,
+            grant,
+            scope: None,
+            refresh_before: DEFAULT_REFRESH_BEFORE,
+            shared: false,
+        }
+    }
+
+    pub fn set_client_secret(&mut self, client_secret: Option<String>) {
+        self.client_secret = client_secret;
+    }
+
+    pub fn set_scope(&mut self, scope: Option<String>) {
+        self.scope = scope;
+    }
+
+    pub fn set_refresh_before(&mut self, refresh_before: u64) {
+        self.refresh_before = refresh_before;
+    }
+
+    pub fn set_shared(&mut self, shared: bool) {
+        self.shared = shared;
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Auth {
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer(String),
+    OAuth2(OAuth2Config),
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>, //SECONDS
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug)]
+struct CachedToken {
+    access_token: String,
+    refresh_at: Option<Instant>, //NONE IF THE TOKEN DOES NOT EXPIRE
+    expires_at: Option<Instant>,
+    refresh_token: Option<String>,
+}
+
+impl CachedToken {
+    fn needs_refresh(&self) -> bool {
+        matches!(self.refresh_at, Some(refresh_at) if Instant::now() >= refresh_at)
+    }
+
+    fn is_expired(&self) -> bool {
+        matches!(self.expires_at, Some(expires_at) if Instant::now() >= expires_at)
+    }
+}
+
+// failed token requests are retried after a backoff that doubles with every failure
+#[derive(Debug)]
+struct TokenFailure {
+    reason: String,
+    failures: u32,
+    retry_at: Instant,
+}
+
+impl TokenFailure {
+    fn get_backoff(failures: u32) -> Duration {
+        let backoff = TOKEN_RETRY_MIN_BACKOFF
+            .saturating_mul(1 << failures.saturating_sub(1).min(16))
+            .min(TOKEN_RETRY_MAX_BACKOFF);
+        Duration::from_millis(backoff)
+    }
+}
+
+#[derive(Debug, Default)]
+struct TokenState {
+    token: Option<CachedToken>,
+    failure: Option<TokenFailure>,
+}
+
+/// An OAuth2 token and its refresh token. Users waiting for a token wait for a single request to the token endpoint.
+#[derive(Debug, Default)]
+pub struct TokenCache {
+    state: Mutex<TokenState>,
+}
+
+impl TokenCache {
+    pub fn new() -> TokenCache {
+        TokenCache::default()
+    }
+}
+
+/// Authenticates the requests of a user.
+#[derive(Clone, Debug, Default)]
+pub struct Authenticator {
+    auth: Arc<Option<Auth>>,
+    token_cache: Arc<TokenCache>,
+}
+
+impl Authenticator {
+    pub fn new(auth: Arc<Option<Auth>>, token_cache: Arc<TokenCache>) -> Authenticator {
+        Authenticator { auth, token_cache }
+    }
+
+    // adds the credentials to the request. returns the reason if no token could be obtained
+    pub async fn authorize(
+        &self,
+        request: RequestBuilder,
+        client: &Client,
+        host: &str,
+    ) -> Result<RequestBuilder, String> {
+        match &*self.auth {
+            None => Ok(request),
+            Some(Auth::Basic { username, password }) => {
+                Ok(request.basic_auth(username, password.as_ref()))
+            }
+            Some(Auth::Bearer(token)) => Ok(request.bearer_auth(token)),
+            Some(Auth::OAuth2(oauth2_config)) => {
+                let access_token = self.get_access_token(oauth2_config, client, host).await?;
+                Ok(request.bearer_auth(access_token))
+            }
+        }
+    }
+
+    async fn get_access_token(
+        &self,
+        oauth2_config: &OAuth2Config,
+        client: &Client,
+        host: &str,
+    ) -> Result<String, String> {
+        let mut state = self.token_cache.state.lock().await;
+        if let Some(ref cached_token) = state.token {
+            if !cached_token.needs_refresh() {
+                return Ok(cached_token.access_token.clone());
+            }
+        }
+        //while backing off, a token that is due for refresh but not expired is still used
+        if let Some(ref failure) = state.failure {
+            if Instant::now() < failure.retry_at {
+                return match state.token {
+                    Some(ref cached_token) if !cached_token.is_expired() => {
+                        Ok(cached_token.access_token.clone())
+                    }
+                    _ => Err(failure.reason.clone()),
+                };
+            }
+        }
+        //a refresh token is tried first, the grant is used if there is none or if it was rejected
+        let refresh_token = state
+            .token
+            .as_ref()
+            .and_then(|cached_token| cached_token.refresh_token.clone());
+        let token_response = match refresh_token {
+            Some(refresh_token) => {
+                match request_token(oauth2_config, client, host, Some(&refresh_token)).await {
+                    Ok(token_response) => Ok(token_response),
+                    Err(_) => request_token(oauth2_config, client, host, None).await,
+                }
+            }
+            None => request_token(oauth2_config, client, host, None).await,
+        };
+        let token_response = match token_response {
+            Ok(token_response) => token_response,
+            Err(reason) => {
+                let failures = state
+                    .failure
+                    .as_ref()
+                    .map_or(1, |failure| failure.failures.saturating_add(1));
+                state.failure = Some(TokenFailure {
+                    reason: reason.clone(),
+                    failures,
+                    retry_at: Instant::now() + TokenFailure::get_backoff(failures),
+                });
+                return match state.token {
+                    Some(ref cached_token) if !cached_token.is_expired() => {
+                        Ok(cached_token.access_token.clone())
+                    }
+                    _ => Err(reason),
+                };
+            }
+        };
+        state.failure = None;
+        let access_token = token_response.access_token.clone();
+        let now = Instant::now();
+        //a token that lives shorter than refresh_before is refreshed after half its lifetime, not on every request
+        let refresh_at = token_response.expires_in.map(|expires_in| {
+            let refresh_before = oauth2_config.refresh_before.min(expires_in / 2);
+            now + Duration::from_secs(expires_in - refresh_before)
+        });
+        let expires_at = token_response
+            .expires_in
+            .map(|expires_in| now + Duration::from_secs(expires_in));
+        //a token endpoint may not send a new refresh token when refreshing
+        let refresh_token = token_response.refresh_token.or_else(|| {
+            state
+                .token
+                .as_ref()
+                .and_then(|cached_token| cached_token.refresh_token.clone())
+        });
+        state.token = Some(CachedToken {
+            access_token: token_response.access_token,
+            refresh_at,
+            expires_at,
+            refresh_token,
+        });
+        Ok(access_token)
+    }
+}
+
+async fn request_token(
+    oauth2_config: &OAuth2Config,
+    client: &Client,
+    host: &str,
+    refresh_token: Option<&str>,
+) -> Result<TokenResponse, String> {
+    let url = if oauth2_config.token_url.starts_with('/') {
+        format!("{}{}", host, oauth2_config.token_url)
+    } else {
+        oauth2_config.token_url.clone()
+    };
+    let mut form: Vec<(&str, &str)> = vec![("client_id", &oauth2_config.client_id)];
+    if let Some(ref client_secret) = oauth2_config.client_secret {
+        form.push(("client_secret", client_secret));
+    }
+    match (refresh_token, &oauth2_config.grant) {
+        (Some(refresh_token), _) => {
+            form.push(("grant_type", "refresh_token"));
+            form.push(("refresh_token", refresh_token));
+        }
+        (None, OAuth2Grant::ClientCredentials) => {
+            form.push(("grant_type", "client_credentials"));
+        }
+        (None, OAuth2Grant::Password { username, password }) => {
+            form.push(("grant_type", "password"));
+            form.push(("username", username));
+            form.push(("password", password));
+        }
+    }
+    if let Some(ref scope) = oauth2_config.scope {
+        form.push(("scope", scope));
+    }
+    let response = client
+        .post(&url)
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| format!("token request failed: {}", e))?;
+    let status_code = response.status();
+    if !status_code.is_success() {
+        return Err(format!("token endpoint returned {}", status_code));
+    }
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("token response could not be read: {}", e))?;
+    serde_json::from_str(&body).map_err(|e| format!("invalid token response: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    // answers every token request with the next of the given responses and keeps the request bodies
+    async fn serve_tokens(
+        responses: Vec<(u16, &'static str)>,
+    ) -> (String, Arc<StdMutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        let bodies = Arc::new(StdMutex::new(Vec::new()));
+        let requests = bodies.clone();
+        tokio::spawn(async move {
+            for (status_code, body) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let content_length = text[..end]
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length: ")
+                                    .map(str::to_string)
+                            })
+                            .map_or(0, |length| length.trim().parse().unwrap());
+                        if text.len() >= end + 4 + content_length {
+                            requests.lock().unwrap().push(text[end + 4..].to_string());
+                            break;
+                        }
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status_code,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (host, bodies)
+    }
+
+    async fn authorize(
+        authenticator: &Authenticator,
+        client: &Client,
+        host: &str,
+    ) -> Result<String, String> {
+        let request = authenticator
+            .authorize(client.get(host), client, host)
+            .await?;
+        let request = request.build().unwrap();
+        Ok(request.headers()["authorization"]
+            .to_str()
+            .unwrap()
+            .to_string())
+    }
+
+    fn create_authenticator() -> Authenticator {
+        let mut oauth2_config = OAuth2Config::new(
+            String::from("/token"),
+            String::from("rocust"),
+            OAuth2Grant::ClientCredentials,
+        );
+        oauth2_config.set_refresh_before(10);
+        Authenticator::new(
+            Arc::new(Some(Auth::OAuth2(oauth2_config))),
+            Arc::new(TokenCache::new()),
+        )
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        assert_eq!(
+            TokenFailure::get_backoff(1),
+            Duration::from_millis(TOKEN_RETRY_MIN_BACKOFF)
+        );
+        assert_eq!(
+            TokenFailure::get_backoff(2),
+            Duration::from_millis(TOKEN_RETRY_MIN_BACKOFF * 2)
+        );
+        assert_eq!(
+            TokenFailure::get_backoff(3),
+            Duration::from_millis(TOKEN_RETRY_MIN_BACKOFF * 4)
+        );
+        assert_eq!(
+            TokenFailure::get_backoff(u32::MAX),
+            Duration::from_millis(TOKEN_RETRY_MAX_BACKOFF)
+        );
+    }
+
+    #[tokio::test]
+    async fn tokens_are_cached_and_refreshed_with_the_refresh_token() {
+        let (host, bodies) = serve_tokens(vec![
+            (
+                200,
+                r#"{"access_token": "a", "expires_in": 100, "refresh_token": "r"}"#,
+            ),
+            (200, r#"{"access_token": "b", "expires_in": 100}"#),
+        ])
+        .await;
+        let client = Client::builder().no_proxy().build().unwrap();
+        let authenticator = create_authenticator();
+        assert_eq!(
+            authorize(&authenticator, &client, &host).await.unwrap(),
+            "Bearer a"
+        );
+        assert_eq!(
+            authorize(&authenticator, &client, &host).await.unwrap(),
+            "Bearer a"
+        );
+        {
+            let mut state = authenticator.token_cache.state.lock().await;
+            let cached_token = state.token.as_mut().unwrap();
+            let refresh_in = cached_token.refresh_at.unwrap() - Instant::now();
+            assert!(refresh_in > Duration::from_secs(80) && refresh_in <= Duration::from_secs(90));
+            cached_token.refresh_at = Some(Instant::now());
+        }
+        assert_eq!(
+            authorize(&authenticator, &client, &host).await.unwrap(),
+            "Bearer b"
+        );
+        let bodies = bodies.lock().unwrap().clone();
+        assert_eq!(bodies.len(), 2);
+        assert!(
+            bodies[0].contains("grant_type=client_credentials"),
+            "{}",
+            bodies[0]
+        );
+        assert!(
+            bodies[1].contains("grant_type=refresh_token&refresh_token=r"),
+            "{}",
+            bodies[1]
+        );
+        // the endpoint sent no new refresh token, the old one is kept
+        let state = authenticator.token_cache.state.lock().await;
+        assert_eq!(
+            state.token.as_ref().unwrap().refresh_token.as_deref(),
+            Some("r")
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_refreshes_back_off_and_keep_the_token_until_it_expires() {
+        let (host, bodies) = serve_tokens(vec![
+            (
+                200,
+                r#"{"access_token": "a", "expires_in": 100, "refresh_token": "r"}"#,
+            ),
+            (500, ""),
+            (500, ""),
+        ])
+        .await;
+        let client = Client::builder().no_proxy().build().unwrap();
+        let authenticator = create_authenticator();
+        assert_eq!(
+            authorize(&authenticator, &client, &host).await.unwrap(),
+            "Bearer a"
+        );
+        authenticator
+            .token_cache
+            .state
+            .lock()
+            .await
+            .token
+            .as_mut()
+            .unwrap()
+            .refresh_at = Some(Instant::now());
+        // the refresh token and the grant are rejected, the token is still valid
+        assert_eq!(
+            authorize(&authenticator, &client, &host).await.unwrap(),
+            "Bearer a"
+        );
+        assert_eq!(bodies.lock().unwrap().len(), 3);
+        // no requests while backing off
+        assert_eq!(
+            authorize(&authenticator, &client, &host).await.unwrap(),
+            "Bearer a"
+        );
+        authenticator
+            .token_cache
+            .state
+            .lock()
+            .await
+            .token
+            .as_mut()
+            .unwrap()
+            .expires_at = Some(Instant::now());
+        let reason = authorize(&authenticator, &client, &host).await.unwrap_err();
+        assert_eq!(reason, "token endpoint returned 500 Internal Server Error");
+        assert_eq!(bodies.lock().unwrap().len(), 3);
+        let state = authenticator.token_cache.state.lock().await;
+        assert_eq!(state.failure.as_ref().unwrap().failures, 1);
+    }
+}
@@ -0,0 +1,329 @@
+use reqwest::{
+    header::CONTENT_TYPE,
+    multipart::{Form, Part as MultipartPart},
+    RequestBuilder,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::{error::Error, path::Path, sync::Arc};
+use tokio::fs;
+
+// file contents are sent to the workers base64 encoded
+fn serialize_base64<S>(content: &Arc<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&base64::encode(&**content))
+}
+
+fn deserialize_base64<'de, D>(deserializer: D) -> Result<Arc<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let encoded = String::deserialize(deserializer)?;
+    let content = base64::decode(encoded).map_err(serde::de::Error::custom)?;
+    Ok(Arc::new(content))
+}
+
+/// The content of a file, read when the test is created.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FileContent {
+    pub file_name: String,
+    pub content_type: Option<String>,
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub content: Arc<Vec<u8>>,
+}
+
+impl FileContent {
+    pub fn new(file_name: String, content_type: Option<String>, content: Vec<u8>) -> FileContent {
+        FileContent {
+            file_name,
+            content_type,
+            content: Arc::new(content),
+        }
+    }
+
+    pub async fn from_file(
+        path: &str,
+        content_type: Option<String>,
+    ) -> Result<FileContent, Box<dyn Error>> {
+        let content = fs::read(path).await?;
+        let file_name = Path::new(path)
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(FileContent::new(file_name, content_type, content))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Part {
+    Text { name: String, value: String },
+    File { name: String, file: FileContent },
+}
+
+/// The body of a request. Text in raw bodies, json strings, form values and text parts is templated.
+#[derive(Clone, Debug)]
+pub enum Body {
+    Raw(String), //SENT AS IS, WITHOUT A CONTENT TYPE
+    Json(Value),
+    Form(Vec<(String, String)>),
+    Multipart(Vec<Part>),
+    File(FileContent), //SENT AS IS, WITH THE CONTENT TYPE OF THE FILE IF SET
+}
+
+impl Body {
+    pub async fn from_file(
+        path: &str,
+        content_type: Option<String>,
+    ) -> Result<Body, Box<dyn Error>> {
+        Ok(Body::File(
+            FileContent::from_file(path, content_type).await?,
+        ))
+    }
+
+    // content types are checked before the test starts, not on every request
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let files: Vec<&FileContent> = match self {
+            Body::File(file) => vec![file],
+            Body::Multipart(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    Part::File { file, .. } => Some(file),
+                    Part::Text { .. } => None,
+                })
+                .collect(),
+            Body::Raw(_) | Body::Json(_) | Body::Form(_) => vec![],
+        };
+        for file in files {
+            if let Some(ref content_type) = file.content_type {
+                MultipartPart::bytes(vec![]).mime_str(content_type)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(
+        &self,
+        request: RequestBuilder,
+        render: impl Fn(&str) -> String,
+    ) -> Result<RequestBuilder, Box<dyn Error>> {
+        match self {
+            Body::Raw(raw) => Ok(request.body(render(raw))),
+            Body::Json(value) => Ok(request
+                .header(CONTENT_TYPE, "application/json")
+                .body(serde_json::to_string(&render_json(value, &render))?)),
+            Body::Form(fields) => {
+                let fields: Vec<(&String, String)> = fields
+                    .iter()
+                    .map(|(key, value)| (key, render(value)))
+                    .collect();
+                Ok(request.form(&fields))
+            }
+            Body::Multipart(parts) => {
+                let mut form = Form::new();
+                for part in parts.iter() {
+                    form = match part {
+                        Part::Text { name, value } => form.text(name.clone(), render(value)),
+                        Part::File { name, file } => {
+                            let mut multipart_part = MultipartPart::bytes(file.content.to_vec())
+                                .file_name(file.file_name.clone());
+                            if let Some(ref content_type) = file.content_type {
+                                multipart_part = multipart_part.mime_str(content_type)?;
+                            }
+                            form.part(name.clone(), multipart_part)
+                        }
+                    };
+                }
+                Ok(request.multipart(form))
+            }
+            Body::File(file) => {
+                let mut request = request.body(file.content.to_vec());
+                if let Some(ref content_type) = file.content_type {
+                    request = request.header(CONTENT_TYPE, content_type);
+                }
+                Ok(request)
+            }
+        }
+    }
+}
+
+// templates in keys are not rendered
+fn render_json(value: &Value, render: &impl Fn(&str) -> String) -> Value {
+    match value {
+        Value::String(text) => Value::String(render(text)),
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| render_json(value, render))
+                .collect(),
+        ),
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| (key.clone(), render_json(value, render)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+impl From<String> for Body {
+    fn from(raw: String) -> Body {
+        Body::Raw(raw)
+    }
+}
+
+impl From<&str> for Body {
+    fn from(raw: &str) -> Body {
+        Body::Raw(raw.to_string())
+    }
+}
+
+// raw bodies are serialized as plain strings
+impl Serialize for Body {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Body::Raw(raw) => serializer.serialize_str(raw),
+            Body::Json(value) => serializer.serialize_newtype_variant("Body", 1, "Json", value),
+            Body::Form(fields) => serializer.serialize_newtype_variant("Body", 2, "Form", fields),
+            Body::Multipart(parts) => {
+                serializer.serialize_newtype_variant("Body", 3, "Multipart", parts)
+            }
+            Body::File(file) => serializer.serialize_newtype_variant("Body", 4, "File", file),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum TypedBody {
+    Raw(String),
+    Json(Value),
+    Form(Vec<(String, String)>),
+    Multipart(Vec<Part>),
+    File(FileContent),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BodyRepr {
+    Raw(String),
+    Typed(TypedBody),
+}
+
+impl<'de> Deserialize<'de> for Body {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match BodyRepr::deserialize(deserializer)? {
+            BodyRepr::Raw(raw) | BodyRepr::Typed(TypedBody::Raw(raw)) => Body::Raw(raw),
+            BodyRepr::Typed(TypedBody::Json(value)) => Body::Json(value),
+            BodyRepr::Typed(TypedBody::Form(fields)) => Body::Form(fields),
+            BodyRepr::Typed(TypedBody::Multipart(parts)) => Body::Multipart(parts),
+            BodyRepr::Typed(TypedBody::File(file)) => Body::File(file),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(body: &Body) -> Body {
+        serde_json::from_str(&serde_json::to_string(body).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn raw_bodies_are_plain_strings() {
+        let body = Body::from("name={{name}}");
+        assert_eq!(serde_json::to_string(&body).unwrap(), r#""name={{name}}""#);
+        assert!(matches!(round_trip(&body), Body::Raw(raw) if raw == "name={{name}}"));
+        let body: Body = serde_json::from_str(r#"{"Raw": "text"}"#).unwrap();
+        assert!(matches!(body, Body::Raw(raw) if raw == "text"));
+    }
+
+    #[test]
+    fn typed_bodies_survive_serialization() {
+        let value = json!({"name": "{{name}}", "tags": [1, "two"]});
+        assert!(
+            matches!(round_trip(&Body::Json(value.clone())), Body::Json(json) if json == value)
+        );
+        let fields = vec![(String::from("name"), String::from("{{name}}"))];
+        assert!(
+            matches!(round_trip(&Body::Form(fields.clone())), Body::Form(form) if form == fields)
+        );
+        let file = FileContent::new(
+            String::from("avatar.png"),
+            Some(String::from("image/png")),
+            vec![0, 159, 146, 150],
+        );
+        let parts = vec![
+            Part::Text {
+                name: String::from("user"),
+                value: String::from("{{name}}"),
+            },
+            Part::File {
+                name: String::from("avatar"),
+                file: file.clone(),
+            },
+        ];
+        match round_trip(&Body::Multipart(parts)) {
+            Body::Multipart(parts) => match &parts[..] {
+                [Part::Text { name, value }, Part::File { file, .. }] => {
+                    assert_eq!((name.as_str(), value.as_str()), ("user", "{{name}}"));
+                    assert_eq!(file.file_name, "avatar.png");
+                    assert_eq!(*file.content, vec![0, 159, 146, 150]);
+                }
+                parts => panic!("unexpected parts {:?}", parts),
+            },
+            body => panic!("unexpected body {:?}", body),
+        }
+        match round_trip(&Body::File(file)) {
+            Body::File(file) => {
+                assert_eq!(file.content_type.as_deref(), Some("image/png"));
+                assert_eq!(*file.content, vec![0, 159, 146, 150]);
+            }
+            body => panic!("unexpected body {:?}", body),
+        }
+    }
+
+    #[test]
+    fn json_bodies_render_values_but_not_keys() {
+        let body = Body::Json(json!({"{{key}}": ["{{value}}", 1]}));
+        let request = body
+            .apply(reqwest::Client::new().post("http://127.0.0.1"), |text| {
+                text.replace("{{value}}", "rendered")
+            })
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        let sent: Value =
+            serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(sent, json!({"{{key}}": ["rendered", 1]}));
+    }
+
+    #[test]
+    fn invalid_content_types_are_rejected() {
+        let file = FileContent::new(
+            String::from("data"),
+            Some(String::from("not a type")),
+            vec![],
+        );
+        assert!(Body::File(file.clone()).validate().is_err());
+        let parts = vec![Part::File {
+            name: String::from("data"),
+            file,
+        }];
+        assert!(Body::Multipart(parts).validate().is_err());
+        assert!(Body::from("text").validate().is_ok());
+    }
+}
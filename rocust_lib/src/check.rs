@@ -0,0 +1,228 @@
+use crate::json_path;
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    error::Error,
+    fmt,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum StatusCodes {
+    Code(u16),
+    Range(u16, u16), //INCLUSIVE
+}
+
+impl StatusCodes {
+    pub fn matches(&self, status_code: u16) -> bool {
+        match self {
+            StatusCodes::Code(code) => *code == status_code,
+            StatusCodes::Range(from, to) => (*from..=*to).contains(&status_code),
+        }
+    }
+}
+
+impl fmt::Display for StatusCodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusCodes::Code(code) => write!(f, "{}", code),
+            StatusCodes::Range(from, to) => write!(f, "{}-{}", from, to),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum CheckKind {
+    Status(Vec<StatusCodes>),
+    BodyContains(String),
+    BodyRegex(String),
+    JsonPathEquals { path: String, value: Value },
+    HeaderPresent(String),
+    MaxResponseTime(u64), //MILLISECONDS
+}
+
+/// What a check or an extractor gets to see of a response.
+pub struct CheckResponse<'a> {
+    pub status_code: u16,
+    pub headers: &'a HeaderMap,
+    pub body: Option<&'a str>,
+    pub response_time: Duration,
+}
+
+/// A named assertion on a response. A failed check counts the request as failed.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub kind: CheckKind,
+    #[serde(skip)]
+    regex: Arc<OnceLock<Option<Regex>>>, //COMPILED ON FIRST USE, SHARED BETWEEN USERS
+}
+
+impl Check {
+    pub fn new(name: String, kind: CheckKind) -> Check {
+        Check {
+            name,
+            kind,
+            regex: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn needs_body(&self) -> bool {
+        matches!(
+            self.kind,
+            CheckKind::BodyContains(_) | CheckKind::BodyRegex(_) | CheckKind::JsonPathEquals { .. }
+        )
+    }
+
+    // the regex and the json path are checked before the test starts instead of failing every request
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        match &self.kind {
+            CheckKind::BodyRegex(pattern) => {
+                Regex::new(pattern)
+                    .map_err(|e| format!("check [{}] has an invalid regex: {}", self.name, e))?;
+            }
+            CheckKind::JsonPathEquals { path, .. } => {
+                json_path::validate(path).map_err(|e| format!("check [{}]: {}", self.name, e))?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn get_regex(&self, pattern: &str) -> Option<&Regex> {
+        self.regex.get_or_init(|| Regex::new(pattern).ok()).as_ref()
+    }
+
+    // returns the reason if the check failed
+    pub fn evaluate(&self, response: &CheckResponse) -> Result<(), String> {
+        let body = response.body.unwrap_or("");
+        match &self.kind {
+            CheckKind::Status(status_codes) => {
+                if status_codes
+                    .iter()
+                    .any(|status_code| status_code.matches(response.status_code))
+                {
+                    Ok(())
+                } else {
+                    Err(format!("unexpected status code {}", response.status_code))
+                }
+            }
+            CheckKind::BodyContains(text) => {
+                if body.contains(text.as_str()) {
+                    Ok(())
+                } else {
+                    Err(format!("body does not contain [{}]", text))
+                }
+            }
+            CheckKind::BodyRegex(pattern) => match self.get_regex(pattern) {
+                Some(regex) if regex.is_match(body) => Ok(()),
+                Some(_) => Err(format!("body does not match [{}]", pattern)),
+                None => Err(format!("invalid regex [{}]", pattern)),
+            },
+            CheckKind::JsonPathEquals { path, value } => {
+                let json: Value = serde_json::from_str(body)
+                    .map_err(|e| format!("body is not valid json: {}", e))?;
+                match json_path::select(&json, path) {
+                    Some(actual) if actual == value => Ok(()),
+                    Some(actual) => Err(format!("[{}] is {} instead of {}", path, actual, value)),
+                    None => Err(format!("[{}] not found", path)),
+                }
+            }
+            CheckKind::HeaderPresent(header) => {
+                if response.headers.contains_key(header.as_str()) {
+                    Ok(())
+                } else {
+                    Err(format!("header [{}] is missing", header))
+                }
+            }
+            CheckKind::MaxResponseTime(max_response_time) => {
+                if response.response_time <= Duration::from_millis(*max_response_time) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "response time {:?} exceeds {}ms",
+                        response.response_time, max_response_time
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn evaluate(check: &Check, status_code: u16, body: &str) -> Result<(), String> {
+        let headers = HeaderMap::new();
+        check.evaluate(&CheckResponse {
+            status_code,
+            headers: &headers,
+            body: Some(body),
+            response_time: Duration::from_millis(10),
+        })
+    }
+
+    #[test]
+    fn invalid_regexes_and_json_paths_are_rejected() {
+        let regex = Check::new(
+            String::from("regex"),
+            CheckKind::BodyRegex(String::from("(")),
+        );
+        assert!(regex.validate().is_err());
+        let path = Check::new(
+            String::from("path"),
+            CheckKind::JsonPathEquals {
+                path: String::from("items[0"),
+                value: json!(1),
+            },
+        );
+        assert!(path.validate().is_err());
+        let valid = Check::new(
+            String::from("regex"),
+            CheckKind::BodyRegex(String::from("^ok")),
+        );
+        assert!(valid.validate().is_ok());
+    }
+
+    #[test]
+    fn checks_report_the_reason() {
+        let status = Check::new(
+            String::from("status"),
+            CheckKind::Status(vec![StatusCodes::Code(201), StatusCodes::Range(200, 204)]),
+        );
+        assert!(evaluate(&status, 204, "").is_ok());
+        assert_eq!(
+            evaluate(&status, 500, ""),
+            Err(String::from("unexpected status code 500"))
+        );
+        let path = Check::new(
+            String::from("path"),
+            CheckKind::JsonPathEquals {
+                path: String::from("$.items[0].id"),
+                value: json!(7),
+            },
+        );
+        assert!(evaluate(&path, 200, r#"{"items": [{"id": 7}]}"#).is_ok());
+        assert_eq!(
+            evaluate(&path, 200, r#"{"items": [{"id": 8}]}"#),
+            Err(String::from("[$.items[0].id] is 8 instead of 7"))
+        );
+        let time = Check::new(String::from("time"), CheckKind::MaxResponseTime(5));
+        assert!(evaluate(&time, 200, "").is_err());
+    }
+}
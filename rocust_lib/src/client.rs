@@ -0,0 +1,202 @@
+use crate::{cookies::CookieJar, EndPoint};
+use reqwest::{Client, ClientBuilder, Identity};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, sync::Arc, time::Duration};
+use tokio::fs;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum HttpVersion {
+    #[default]
+    Auto,
+    Http1,
+    Http2, //PRIOR KNOWLEDGE, THE SERVER MUST SUPPORT HTTP/2 WITHOUT NEGOTIATION
+}
+
+/// A PKCS#12 client certificate. The archive is kept base64 encoded, so it can be sent to the workers.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClientCertificate {
+    pub pkcs12: String, //BASE64
+    pub password: String,
+}
+
+impl ClientCertificate {
+    pub fn new(der: &[u8], password: String) -> ClientCertificate {
+        ClientCertificate {
+            pkcs12: base64::encode(der),
+            password,
+        }
+    }
+
+    pub async fn from_file(path: &str, password: String) -> Result<Self, Box<dyn Error>> {
+        let der = fs::read(path).await?;
+        Ok(ClientCertificate::new(&der, password))
+    }
+
+    pub fn get_identity(&self) -> Result<Identity, Box<dyn Error>> {
+        let der = base64::decode(&self.pkcs12)?;
+        let identity = Identity::from_pkcs12_der(&der, &self.password)?;
+        Ok(identity)
+    }
+}
+
+// unset values fall back to the defaults of reqwest, or are taken from the test when used as an endpoint override
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClientConfig {
+    pub timeout: Option<u64>,         //MILLISECONDS
+    pub connect_timeout: Option<u64>, //MILLISECONDS
+    pub keep_alive: Option<bool>,
+    pub http_version: Option<HttpVersion>,
+    pub accept_invalid_certs: Option<bool>,
+    pub client_certificate: Option<ClientCertificate>,
+}
+
+impl ClientConfig {
+    pub fn new() -> ClientConfig {
+        ClientConfig::default()
+    }
+
+    pub fn set_timeout(&mut self, timeout: Option<u64>) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_connect_timeout(&mut self, connect_timeout: Option<u64>) {
+        self.connect_timeout = connect_timeout;
+    }
+
+    pub fn set_keep_alive(&mut self, keep_alive: Option<bool>) {
+        self.keep_alive = keep_alive;
+    }
+
+    pub fn set_http_version(&mut self, http_version: Option<HttpVersion>) {
+        self.http_version = http_version;
+    }
+
+    pub fn set_accept_invalid_certs(&mut self, accept_invalid_certs: Option<bool>) {
+        self.accept_invalid_certs = accept_invalid_certs;
+    }
+
+    pub fn set_client_certificate(&mut self, client_certificate: Option<ClientCertificate>) {
+        self.client_certificate = client_certificate;
+    }
+
+    // values set in the overrides replace the values of this config
+    pub fn with_overrides(&self, overrides: &ClientConfig) -> ClientConfig {
+        ClientConfig {
+            timeout: overrides.timeout.or(self.timeout),
+            connect_timeout: overrides.connect_timeout.or(self.connect_timeout),
+            keep_alive: overrides.keep_alive.or(self.keep_alive),
+            http_version: overrides
+                .http_version
+                .clone()
+                .or_else(|| self.http_version.clone()),
+            accept_invalid_certs: overrides.accept_invalid_certs.or(self.accept_invalid_certs),
+            client_certificate: overrides
+                .client_certificate
+                .clone()
+                .or_else(|| self.client_certificate.clone()),
+        }
+    }
+
+    pub fn build_client(&self) -> Result<Client, Box<dyn Error>> {
+        Ok(self.create_builder()?.build()?)
+    }
+
+    // the cookies of the client are kept in the jar, without a jar the client keeps no cookies
+    pub fn build_client_with_cookies(
+        &self,
+        cookie_jar: &Option<Arc<CookieJar>>,
+    ) -> Result<Client, Box<dyn Error>> {
+        let mut builder = self.create_builder()?;
+        if let Some(cookie_jar) = cookie_jar {
+            builder = builder.cookie_provider(cookie_jar.clone());
+        }
+        Ok(builder.build()?)
+    }
+
+    fn create_builder(&self) -> Result<ClientBuilder, Box<dyn Error>> {
+        let mut builder = ClientBuilder::new();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(Duration::from_millis(timeout));
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(Duration::from_millis(connect_timeout));
+        }
+        if let Some(false) = self.keep_alive {
+            //no idle connections are kept, every request opens a new connection
+            builder = builder.pool_max_idle_per_host(0);
+        }
+        match self.http_version {
+            Some(HttpVersion::Http1) => builder = builder.http1_only(),
+            Some(HttpVersion::Http2) => builder = builder.http2_prior_knowledge(),
+            Some(HttpVersion::Auto) | None => {}
+        }
+        if let Some(accept_invalid_certs) = self.accept_invalid_certs {
+            builder = builder.danger_accept_invalid_certs(accept_invalid_certs);
+        }
+        if let Some(ref client_certificate) = self.client_certificate {
+            builder = builder.identity(client_certificate.get_identity()?);
+        }
+        Ok(builder)
+    }
+}
+
+/// The clients of a user: one built from the client config of the test and one per distinct endpoint override.
+/// All clients of a user share the cookie jar of the user.
+#[derive(Clone, Debug)]
+pub struct Clients {
+    client: Client,
+    overrides: Vec<(ClientConfig, Client)>, //ENDPOINT OVERRIDES -> CLIENT, ONE PER DISTINCT OVERRIDE
+    cookie_jar: Option<Arc<CookieJar>>,
+}
+
+impl Clients {
+    pub fn new<'a>(
+        client_config: &ClientConfig,
+        cookie_jar: Option<Arc<CookieJar>>,
+        endpoints: impl Iterator<Item = &'a EndPoint>,
+    ) -> Result<Clients, Box<dyn Error>> {
+        let client = client_config.build_client_with_cookies(&cookie_jar)?;
+        let mut overrides: Vec<(ClientConfig, Client)> = Vec::new();
+        for endpoint in endpoints {
+            if let Some(ref endpoint_client_config) = endpoint.client_config {
+                if overrides
+                    .iter()
+                    .any(|(overrides, _)| overrides == endpoint_client_config)
+                {
+                    continue;
+                }
+                let client = client_config
+                    .with_overrides(endpoint_client_config)
+                    .build_client_with_cookies(&cookie_jar)?;
+                overrides.push((endpoint_client_config.clone(), client));
+            }
+        }
+        Ok(Clients {
+            client,
+            overrides,
+            cookie_jar,
+        })
+    }
+
+    // the client of the test, used for requests that are not made to an endpoint
+    pub fn get_default_client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn get_cookie_jar(&self) -> &Option<Arc<CookieJar>> {
+        &self.cookie_jar
+    }
+
+    // endpoints that were not known when the clients were created use the client of the test.
+    // a test has a handful of distinct overrides, comparing them is cheaper than hashing the certificates
+    pub fn get_client(&self, endpoint: &EndPoint) -> &Client {
+        let Some(ref endpoint_client_config) = endpoint.client_config else {
+            return &self.client;
+        };
+        self.overrides
+            .iter()
+            .find(|(overrides, _)| overrides == endpoint_client_config)
+            .map(|(_, client)| client)
+            .unwrap_or(&self.client)
+    }
+}
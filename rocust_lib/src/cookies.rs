@@ -0,0 +1,163 @@
+use parking_lot::RwLock;
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    header::HeaderValue,
+};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt};
+use url::Url;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SeedCookie {
+    pub name: String,
+    pub value: String,
+    pub url: Option<String>, //THE COOKIE IS SENT TO THIS URL, TO THE HOST OF THE TEST IF NONE
+}
+
+impl SeedCookie {
+    pub fn new(name: String, value: String, url: Option<String>) -> SeedCookie {
+        SeedCookie { name, value, url }
+    }
+}
+
+// a test without a cookie config has no cookie jar, responses setting cookies are ignored
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CookieConfig {
+    pub seeds: Vec<SeedCookie>,
+    pub reset_per_scenario: bool, //EVERY SCENARIO RUN STARTS WITH THE SEEDED COOKIES ONLY
+}
+
+impl CookieConfig {
+    pub fn new() -> CookieConfig {
+        CookieConfig::default()
+    }
+
+    pub fn set_seeds(&mut self, seeds: Vec<SeedCookie>) {
+        self.seeds = seeds;
+    }
+
+    pub fn add_seed(&mut self, seed: SeedCookie) {
+        self.seeds.push(seed);
+    }
+
+    pub fn set_reset_per_scenario(&mut self, reset_per_scenario: bool) {
+        self.reset_per_scenario = reset_per_scenario;
+    }
+}
+
+/// The cookies of a user, shared by all clients of the user.
+pub struct CookieJar {
+    jar: RwLock<Jar>,
+    seeds: Vec<(String, Url)>, //SET-COOKIE VALUE, URL
+    reset_per_scenario: bool,
+}
+
+impl CookieJar {
+    pub fn new(cookie_config: &CookieConfig, host: &str) -> Result<CookieJar, Box<dyn Error>> {
+        let mut seeds = Vec::new();
+        for seed in cookie_config.seeds.iter() {
+            let url = Url::parse(seed.url.as_deref().unwrap_or(host))?;
+            seeds.push((format!("{}={}; Path=/", seed.name, seed.value), url));
+        }
+        let cookie_jar = CookieJar {
+            jar: RwLock::new(Jar::default()),
+            seeds,
+            reset_per_scenario: cookie_config.reset_per_scenario,
+        };
+        cookie_jar.reset();
+        Ok(cookie_jar)
+    }
+
+    // drops all cookies but the seeded ones
+    pub fn reset(&self) {
+        let jar = Jar::default();
+        for (cookie, url) in self.seeds.iter() {
+            jar.add_cookie_str(cookie, url);
+        }
+        *self.jar.write() = jar;
+    }
+
+    pub fn resets_per_scenario(&self) -> bool {
+        self.reset_per_scenario
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        self.jar.read().set_cookies(cookie_headers, url);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        self.jar.read().cookies(url)
+    }
+}
+
+impl fmt::Debug for CookieJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieJar")
+            .field("seeds", &self.seeds)
+            .field("reset_per_scenario", &self.reset_per_scenario)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookies(cookie_jar: &CookieJar, url: &str) -> Option<String> {
+        cookie_jar
+            .cookies(&Url::parse(url).unwrap())
+            .map(|cookies| cookies.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn seeded_cookies_go_to_their_url_or_the_host() {
+        let mut cookie_config = CookieConfig::new();
+        cookie_config.add_seed(SeedCookie::new(
+            String::from("session"),
+            String::from("1"),
+            None,
+        ));
+        cookie_config.add_seed(SeedCookie::new(
+            String::from("region"),
+            String::from("eu"),
+            Some(String::from("http://other.test")),
+        ));
+        let cookie_jar = CookieJar::new(&cookie_config, "http://host.test").unwrap();
+        assert_eq!(
+            cookies(&cookie_jar, "http://host.test/cart"),
+            Some(String::from("session=1"))
+        );
+        assert_eq!(
+            cookies(&cookie_jar, "http://other.test"),
+            Some(String::from("region=eu"))
+        );
+        assert!(CookieJar::new(&cookie_config, "not a url").is_err());
+    }
+
+    #[test]
+    fn reset_keeps_only_the_seeded_cookies() {
+        let mut cookie_config = CookieConfig::new();
+        cookie_config.add_seed(SeedCookie::new(
+            String::from("session"),
+            String::from("1"),
+            None,
+        ));
+        cookie_config.set_reset_per_scenario(true);
+        let cookie_jar = CookieJar::new(&cookie_config, "http://host.test").unwrap();
+        assert!(cookie_jar.resets_per_scenario());
+        let url = Url::parse("http://host.test").unwrap();
+        let set_cookie = HeaderValue::from_static("cart=3; Path=/");
+        cookie_jar.set_cookies(&mut std::iter::once(&set_cookie), &url);
+        assert_eq!(
+            cookies(&cookie_jar, "http://host.test"),
+            Some(String::from("session=1; cart=3"))
+        );
+        cookie_jar.reset();
+        assert_eq!(
+            cookies(&cookie_jar, "http://host.test"),
+            Some(String::from("session=1"))
+        );
+    }
+}
@@ -1,11 +1,23 @@
 use parking_lot::RwLock;
-use crate::{Results, HasResults};
+use crate::{
+    body::Body,
+    check::CheckKind, errors::ErrorKind, extractor::Extractor, Check, ClientConfig, HasResults,
+    Results,
+};
 use serde::{
     de::{MapAccess, Visitor},
     ser::SerializeStruct,
     Deserialize, Deserializer, Serialize, Serializer,
 };
-use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
+
+pub const DEFAULT_WEIGHT: u32 = 1;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Method {
@@ -13,6 +25,26 @@ pub enum Method {
     POST,
     PUT,
     DELETE,
+    PATCH,
+    HEAD,
+    OPTIONS,
+    Custom(String), //ANY OTHER VERB, E.G. PURGE
+}
+
+impl Method {
+    pub fn to_reqwest_method(&self) -> Result<reqwest::Method, Box<dyn Error>> {
+        let method = match self {
+            Method::GET => reqwest::Method::GET,
+            Method::POST => reqwest::Method::POST,
+            Method::PUT => reqwest::Method::PUT,
+            Method::DELETE => reqwest::Method::DELETE,
+            Method::PATCH => reqwest::Method::PATCH,
+            Method::HEAD => reqwest::Method::HEAD,
+            Method::OPTIONS => reqwest::Method::OPTIONS,
+            Method::Custom(method) => reqwest::Method::from_bytes(method.as_bytes())?,
+        };
+        Ok(method)
+    }
 }
 
 impl fmt::Display for Method {
@@ -22,6 +54,10 @@ impl fmt::Display for Method {
             Method::POST => write!(f, "POST"),
             Method::PUT => write!(f, "PUT"),
             Method::DELETE => write!(f, "DELETE"),
+            Method::PATCH => write!(f, "PATCH"),
+            Method::HEAD => write!(f, "HEAD"),
+            Method::OPTIONS => write!(f, "OPTIONS"),
+            Method::Custom(method) => write!(f, "{}", method),
         }
     }
 }
@@ -32,7 +68,11 @@ pub struct EndPoint {
     pub url: String,
     pub headers: Option<HashMap<String, String>>,
     pub params: Option<Vec<(String, String)>>,
-    pub body: Option<String>,
+    pub body: Option<Body>,
+    pub checks: Vec<Check>,
+    pub extractors: Vec<Extractor>, //RUN AFTER THE CHECKS PASSED
+    pub weight: u32, //RELATIVE TO THE OTHER ENDPOINTS OF THE TEST, 0 DISABLES THE ENDPOINT
+    pub client_config: Option<ClientConfig>, //OVERRIDES THE CLIENT CONFIG OF THE TEST
     pub results: Arc<RwLock<Results>>, //ENDPOINT RESULTS
 }
 
@@ -41,12 +81,16 @@ impl Serialize for EndPoint {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("EndPoint", 6)?;
+        let mut state = serializer.serialize_struct("EndPoint", 10)?;
         state.serialize_field("method", &self.method)?;
         state.serialize_field("url", &self.url)?;
         state.serialize_field("headers", &self.headers)?;
         state.serialize_field("params", &self.params)?;
         state.serialize_field("body", &self.body)?;
+        state.serialize_field("checks", &self.checks)?;
+        state.serialize_field("extractors", &self.extractors)?;
+        state.serialize_field("weight", &self.weight)?;
+        state.serialize_field("client_config", &self.client_config)?;
         state.serialize_field("results", &*self.results.read())?;
         state.end()
     }
@@ -67,6 +111,10 @@ impl<'de> Deserialize<'de> for EndPoint {
             Headers,
             Params,
             Body,
+            Checks,
+            Extractors,
+            Weight,
+            ClientConfig,
             Results,
         }
         impl<'de> Visitor<'de> for EndPointVisitor {
@@ -84,7 +132,11 @@ impl<'de> Deserialize<'de> for EndPoint {
                 let mut url: Option<String> = None;
                 let mut headers: Option<Option<HashMap<String, String>>> = None;
                 let mut params: Option<Option<Vec<(String, String)>>> = None;
-                let mut body: Option<Option<String>> = None;
+                let mut body: Option<Option<Body>> = None;
+                let mut checks: Option<Vec<Check>> = None;
+                let mut extractors: Option<Vec<Extractor>> = None;
+                let mut weight: Option<u32> = None;
+                let mut client_config: Option<Option<ClientConfig>> = None;
                 let mut results: Option<Results> = None;
 
                 while let Some(key) = map.next_key()? {
@@ -119,6 +171,30 @@ impl<'de> Deserialize<'de> for EndPoint {
                             }
                             body = Some(map.next_value()?);
                         }
+                        Field::Checks => {
+                            if checks.is_some() {
+                                return Err(serde::de::Error::duplicate_field("checks"));
+                            }
+                            checks = Some(map.next_value()?);
+                        }
+                        Field::Extractors => {
+                            if extractors.is_some() {
+                                return Err(serde::de::Error::duplicate_field("extractors"));
+                            }
+                            extractors = Some(map.next_value()?);
+                        }
+                        Field::Weight => {
+                            if weight.is_some() {
+                                return Err(serde::de::Error::duplicate_field("weight"));
+                            }
+                            weight = Some(map.next_value()?);
+                        }
+                        Field::ClientConfig => {
+                            if client_config.is_some() {
+                                return Err(serde::de::Error::duplicate_field("client_config"));
+                            }
+                            client_config = Some(map.next_value()?);
+                        }
                         Field::Results => {
                             if results.is_some() {
                                 return Err(serde::de::Error::duplicate_field("logfile_path"));
@@ -132,6 +208,11 @@ impl<'de> Deserialize<'de> for EndPoint {
                 let headers = headers.ok_or_else(|| serde::de::Error::missing_field("headers"))?;
                 let params = params.ok_or_else(|| serde::de::Error::missing_field("params"))?;
                 let body = body.ok_or_else(|| serde::de::Error::missing_field("body"))?;
+                // optional, older endpoints have none of these
+                let checks = checks.unwrap_or_default();
+                let extractors = extractors.unwrap_or_default();
+                let weight = weight.unwrap_or(DEFAULT_WEIGHT);
+                let client_config = client_config.unwrap_or_default();
                 let results = results.ok_or_else(|| serde::de::Error::missing_field("results"))?;
 
                 Ok(EndPoint {
@@ -139,14 +220,28 @@ impl<'de> Deserialize<'de> for EndPoint {
                     url,
                     params,
                     body,
+                    checks,
+                    extractors,
+                    weight,
+                    client_config,
                     results: Arc::new(RwLock::new(results)),
                     headers,
                 })
             }
         }
-        const FIELDS: &'static [&'static str] =
-            &["method", "url", "headers", "params", "body", "results"];
-        deserializer.deserialize_struct("EndPoint", &FIELDS, EndPointVisitor)
+        const FIELDS: &[&str] = &[
+            "method",
+            "url",
+            "headers",
+            "params",
+            "body",
+            "checks",
+            "extractors",
+            "weight",
+            "client_config",
+            "results",
+        ];
+        deserializer.deserialize_struct("EndPoint", FIELDS, EndPointVisitor)
     }
 }
 
@@ -162,7 +257,11 @@ impl EndPoint {
             method,
             url,
             params,
-            body,
+            body: body.map(Body::Raw),
+            checks: Vec::new(),
+            extractors: Vec::new(),
+            weight: DEFAULT_WEIGHT,
+            client_config: None,
             results: Arc::new(RwLock::new(Results::new())),
             headers,
         }
@@ -196,6 +295,45 @@ impl EndPoint {
         EndPoint::new(Method::DELETE, url, headers, None, None)
     }
 
+    pub fn new_patch(
+        url: String,
+        headers: Option<HashMap<String, String>>,
+        body: Option<String>,
+    ) -> EndPoint {
+        EndPoint::new(Method::PATCH, url, headers, None, body)
+    }
+
+    pub fn new_head(
+        url: String,
+        headers: Option<HashMap<String, String>>,
+        params: Option<Vec<(String, String)>>,
+    ) -> EndPoint {
+        EndPoint::new(Method::HEAD, url, headers, params, None)
+    }
+
+    pub fn new_options(url: String, headers: Option<HashMap<String, String>>) -> EndPoint {
+        EndPoint::new(Method::OPTIONS, url, headers, None, None)
+    }
+
+    pub fn new_custom(
+        method: String,
+        url: String,
+        headers: Option<HashMap<String, String>>,
+        params: Option<Vec<(String, String)>>,
+        body: Option<String>,
+    ) -> EndPoint {
+        EndPoint::new(Method::Custom(method), url, headers, params, body)
+    }
+
+    // params and bodies are allowed on every method
+    pub fn set_params(&mut self, params: Option<Vec<(String, String)>>) {
+        self.params = params;
+    }
+
+    pub fn set_body(&mut self, body: Option<Body>) {
+        self.body = body;
+    }
+
     pub fn get_method(&self) -> &Method {
         &self.method
     }
@@ -204,6 +342,12 @@ impl EndPoint {
         &self.url
     }
 
+    // endpoints with the same url and different methods are different endpoints,
+    // so results, history and thresholds are keyed by method and url
+    pub fn get_id(&self) -> String {
+        format!("{} {}", self.method, self.url)
+    }
+
     pub fn get_results(&self) -> &Arc<RwLock<Results>> {
         &self.results
     }
@@ -212,9 +356,98 @@ impl EndPoint {
         &self.params
     }
 
-    pub fn get_body(&self) -> &Option<String> {
+    pub fn get_body(&self) -> &Option<Body> {
         &self.body
     }
+
+    pub fn get_checks(&self) -> &Vec<Check> {
+        &self.checks
+    }
+
+    pub fn set_checks(&mut self, checks: Vec<Check>) {
+        self.checks = checks;
+    }
+
+    pub fn add_check(&mut self, check: Check) {
+        self.checks.push(check);
+    }
+
+    pub fn get_extractors(&self) -> &Vec<Extractor> {
+        &self.extractors
+    }
+
+    pub fn set_extractors(&mut self, extractors: Vec<Extractor>) {
+        self.extractors = extractors;
+    }
+
+    pub fn add_extractor(&mut self, extractor: Extractor) {
+        self.extractors.push(extractor);
+    }
+
+    pub fn get_weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn set_weight(&mut self, weight: u32) {
+        self.weight = weight;
+    }
+
+    pub fn get_client_config(&self) -> &Option<ClientConfig> {
+        &self.client_config
+    }
+
+    pub fn set_client_config(&mut self, client_config: Option<ClientConfig>) {
+        self.client_config = client_config;
+    }
+
+    pub fn needs_body(&self) -> bool {
+        self.checks.iter().any(|check| check.needs_body())
+            || self
+                .extractors
+                .iter()
+                .any(|extractor| extractor.needs_body())
+    }
+
+    // without a status check, responses with status codes from 200 to 399 are successful
+    pub fn has_status_check(&self) -> bool {
+        self.checks
+            .iter()
+            .any(|check| matches!(check.kind, CheckKind::Status(_)))
+    }
+}
+
+// the share of the requests the endpoint actually got among the endpoints, connection errors included.
+// every endpoint has its own results, so endpoints with the same url get their own share
+pub fn get_actual_mix<'a>(
+    endpoint: &EndPoint,
+    endpoints: impl Iterator<Item = &'a EndPoint>,
+) -> f64 {
+    let sent = |endpoint: &EndPoint| {
+        let results = endpoint.get_results().read();
+        results
+            .total_requests
+            .saturating_add(results.total_connection_errors)
+    };
+    let total_sent = endpoints.map(sent).fold(0u64, |total, sent| total.saturating_add(sent));
+    if total_sent == 0 {
+        return 0.0;
+    }
+    sent(endpoint) as f64 / total_sent as f64
+}
+
+// the results of endpoints with the same id would be merged
+pub fn validate_unique_ids<'a>(
+    endpoints: impl Iterator<Item = &'a EndPoint>,
+) -> Result<(), Box<dyn Error>> {
+    let mut ids = HashSet::new();
+    for endpoint in endpoints {
+        let id = endpoint.get_id();
+        if ids.contains(&id) {
+            return Err(format!("endpoint [{}] is defined more than once", id).into());
+        }
+        ids.insert(id);
+    }
+    Ok(())
 }
 
 impl fmt::Display for EndPoint {
@@ -230,16 +463,20 @@ impl fmt::Display for EndPoint {
 }
 
 impl HasResults for EndPoint {
-    fn add_response_time(&self, response_time: u32) {
+    fn add_response_time(&self, response_time: u64) {
         self.results.write().add_response_time(response_time);
     }
 
-    fn add_failed(&self) {
-        self.results.write().add_failed();
+    fn add_failed(&self, status_code: u16) {
+        self.results.write().add_failed(status_code);
     }
 
-    fn add_connection_error(&self) {
-        self.results.write().add_connection_error();
+    fn add_failed_check(&self, name: &str, reason: &str) {
+        self.results.write().add_failed_check(name, reason);
+    }
+
+    fn add_connection_error(&self, kind: ErrorKind, message: &str) {
+        self.results.write().add_connection_error(kind, message);
     }
 
     fn set_requests_per_second(&self, requests_per_second: f64) {
@@ -258,6 +495,16 @@ impl HasResults for EndPoint {
             .calculate_failed_requests_per_second(elapsed);
     }
 
+    fn calculate_current_requests_per_second(&self, elapsed: &Duration, window: &Duration) {
+        self.results
+            .write()
+            .calculate_current_requests_per_second(elapsed, window);
+    }
+
+    fn calculate_percentiles(&self) {
+        self.results.write().calculate_percentiles();
+    }
+
     fn get_results(&self) -> Arc<RwLock<Results>> {
         self.results.clone()
     }
@@ -267,3 +514,46 @@ impl HasResults for EndPoint {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_methods_are_sent_as_given() {
+        let endpoint =
+            EndPoint::new_custom(String::from("PURGE"), String::from("/cache"), None, None, None);
+        assert_eq!(endpoint.get_id(), "PURGE /cache");
+        assert_eq!(endpoint.get_method().to_reqwest_method().unwrap().as_str(), "PURGE");
+        let method = Method::Custom(String::from("NOT A METHOD"));
+        assert!(method.to_reqwest_method().is_err());
+    }
+
+    #[test]
+    fn same_urls_with_different_methods_are_different_endpoints() {
+        let endpoints = [
+            EndPoint::new_get(String::from("/cache"), None, None),
+            EndPoint::new_custom(String::from("PURGE"), String::from("/cache"), None, None, None),
+        ];
+        assert!(validate_unique_ids(endpoints.iter()).is_ok());
+        let endpoints = [
+            EndPoint::new_custom(String::from("PURGE"), String::from("/cache"), None, None, None),
+            EndPoint::new_custom(String::from("PURGE"), String::from("/cache"), None, None, None),
+        ];
+        assert!(validate_unique_ids(endpoints.iter()).is_err());
+    }
+
+    #[test]
+    fn custom_methods_survive_serialization() {
+        let mut endpoint =
+            EndPoint::new_custom(String::from("PURGE"), String::from("/cache"), None, None, None);
+        endpoint.set_params(Some(vec![(String::from("all"), String::from("true"))]));
+        let json = serde_json::to_string(&endpoint).unwrap();
+        let endpoint: EndPoint = serde_json::from_str(&json).unwrap();
+        assert_eq!(endpoint.get_id(), "PURGE /cache");
+        assert_eq!(
+            endpoint.get_params(),
+            &Some(vec![(String::from("all"), String::from("true"))])
+        );
+    }
+}
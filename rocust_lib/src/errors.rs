@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error, fmt};
+
+pub const MAX_ERROR_SAMPLES: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum ErrorKind {
+    Dns,
+    ConnectionRefused,
+    Tls,
+    Timeout,
+    Connect, //ANY OTHER ERROR WHILE CONNECTING
+    BodyRead,
+    Check,
+    Step, //A FAILED STEP OF A SCENARIO
+    Auth, //NO TOKEN COULD BE OBTAINED OR REFRESHED
+    Other,
+}
+
+impl ErrorKind {
+    // reqwest does not expose the cause of an error, so the source chain is inspected
+    pub fn from_reqwest_error(error: &reqwest::Error) -> ErrorKind {
+        if error.is_timeout() {
+            return ErrorKind::Timeout;
+        }
+        let mut chain = error.to_string().to_lowercase();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            if let Some(io_error) = cause.downcast_ref::<std::io::Error>() {
+                match io_error.kind() {
+                    std::io::ErrorKind::ConnectionRefused => return ErrorKind::ConnectionRefused,
+                    std::io::ErrorKind::TimedOut => return ErrorKind::Timeout,
+                    _ => {}
+                }
+            }
+            chain.push_str(&cause.to_string().to_lowercase());
+            source = cause.source();
+        }
+        if chain.contains("dns error") || chain.contains("failed to lookup address") {
+            ErrorKind::Dns
+        } else if chain.contains("connection refused") {
+            ErrorKind::ConnectionRefused
+        } else if chain.contains("tls")
+            || chain.contains("ssl")
+            || chain.contains("certificate")
+            || chain.contains("handshake")
+        {
+            ErrorKind::Tls
+        } else if error.is_connect() {
+            ErrorKind::Connect
+        } else if error.is_body() || error.is_decode() {
+            ErrorKind::BodyRead
+        } else {
+            ErrorKind::Other
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Dns => write!(f, "DNS"),
+            ErrorKind::ConnectionRefused => write!(f, "CONNECTION REFUSED"),
+            ErrorKind::Tls => write!(f, "TLS"),
+            ErrorKind::Timeout => write!(f, "TIMEOUT"),
+            ErrorKind::Connect => write!(f, "CONNECT"),
+            ErrorKind::BodyRead => write!(f, "BODY READ"),
+            ErrorKind::Check => write!(f, "CHECK"),
+            ErrorKind::Step => write!(f, "STEP"),
+            ErrorKind::Auth => write!(f, "AUTH"),
+            ErrorKind::Other => write!(f, "OTHER"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ErrorSample {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub count: u64,
+}
+
+/// Why requests failed: failed requests by status code, errors by kind and the first few distinct messages.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ErrorBreakdown {
+    pub status_codes: HashMap<u16, u64>,
+    pub kinds: HashMap<ErrorKind, u64>,
+    pub samples: Vec<ErrorSample>, //AT MOST MAX_ERROR_SAMPLES
+}
+
+impl ErrorBreakdown {
+    pub fn new() -> ErrorBreakdown {
+        ErrorBreakdown::default()
+    }
+
+    pub fn add_status_code(&mut self, status_code: u16) {
+        self.add_status_code_n(status_code, 1);
+    }
+
+    pub fn add_error(&mut self, kind: ErrorKind, message: &str) {
+        self.add_error_n(kind, 1);
+        self.add_sample(kind, message, 1);
+    }
+
+    fn add_status_code_n(&mut self, status_code: u16, count: u64) {
+        let entry = self.status_codes.entry(status_code).or_insert(0);
+        *entry = entry.saturating_add(count);
+    }
+
+    fn add_error_n(&mut self, kind: ErrorKind, count: u64) {
+        let entry = self.kinds.entry(kind).or_insert(0);
+        *entry = entry.saturating_add(count);
+    }
+
+    // samples with a known message are counted, new messages are dropped once the samples are full
+    fn add_sample(&mut self, kind: ErrorKind, message: &str, count: u64) {
+        if let Some(sample) = self
+            .samples
+            .iter_mut()
+            .find(|sample| sample.kind == kind && sample.message == message)
+        {
+            sample.count = sample.count.saturating_add(count);
+        } else if self.samples.len() < MAX_ERROR_SAMPLES {
+            self.samples.push(ErrorSample {
+                kind,
+                message: message.to_string(),
+                count,
+            });
+        }
+    }
+
+    pub fn merge(&mut self, other: &ErrorBreakdown) {
+        for (status_code, count) in other.status_codes.iter() {
+            self.add_status_code_n(*status_code, *count);
+        }
+        for (kind, count) in other.kinds.iter() {
+            self.add_error_n(*kind, *count);
+        }
+        for sample in other.samples.iter() {
+            self.add_sample(sample.kind, &sample.message, sample.count);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.status_codes.is_empty() && self.kinds.is_empty()
+    }
+
+    pub fn get_sorted_status_codes(&self) -> Vec<(u16, u64)> {
+        let mut status_codes: Vec<(u16, u64)> = self
+            .status_codes
+            .iter()
+            .map(|(status_code, count)| (*status_code, *count))
+            .collect();
+        status_codes.sort();
+        status_codes
+    }
+
+    pub fn get_sorted_kinds(&self) -> Vec<(ErrorKind, u64)> {
+        let mut kinds: Vec<(ErrorKind, u64)> = self
+            .kinds
+            .iter()
+            .map(|(kind, count)| (*kind, *count))
+            .collect();
+        kinds.sort();
+        kinds
+    }
+}
+
+impl fmt::Display for ErrorBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut errors: Vec<String> = self
+            .get_sorted_status_codes()
+            .iter()
+            .map(|(status_code, count)| format!("{}: {}", status_code, count))
+            .collect();
+        errors.extend(
+            self.get_sorted_kinds()
+                .iter()
+                .map(|(kind, count)| format!("{}: {}", kind, count)),
+        );
+        write!(f, "{}", errors.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    async fn request_error(url: &str) -> reqwest::Error {
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        client.get(url).send().await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn refused_connections_are_told_apart_from_timeouts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        // the listener accepts the connection but never responds
+        let error = request_error(&format!("http://{}", address)).await;
+        assert_eq!(ErrorKind::from_reqwest_error(&error), ErrorKind::Timeout);
+        drop(listener);
+        let error = request_error(&format!("http://{}", address)).await;
+        assert_eq!(
+            ErrorKind::from_reqwest_error(&error),
+            ErrorKind::ConnectionRefused
+        );
+    }
+
+    #[test]
+    fn merged_breakdowns_add_up_and_keep_few_samples() {
+        let mut breakdown = ErrorBreakdown::new();
+        breakdown.add_status_code(500);
+        breakdown.add_error(ErrorKind::Timeout, "timed out");
+        let mut other = ErrorBreakdown::new();
+        other.add_status_code(500);
+        other.add_status_code(404);
+        other.add_error(ErrorKind::Timeout, "timed out");
+        for index in 0..MAX_ERROR_SAMPLES {
+            other.add_error(ErrorKind::Other, &index.to_string());
+        }
+        breakdown.merge(&other);
+        assert_eq!(
+            breakdown.get_sorted_status_codes(),
+            vec![(404, 1), (500, 2)]
+        );
+        assert_eq!(
+            breakdown.get_sorted_kinds(),
+            vec![
+                (ErrorKind::Timeout, 2),
+                (ErrorKind::Other, MAX_ERROR_SAMPLES as u64)
+            ]
+        );
+        assert_eq!(breakdown.samples.len(), MAX_ERROR_SAMPLES);
+        assert_eq!(breakdown.samples[0].count, 2);
+        assert_eq!(
+            breakdown.to_string(),
+            "404: 1, 500: 2, TIMEOUT: 2, OTHER: 5"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{check::CheckResponse, json_path};
+use regex::Regex;
+use reqwest::header::SET_COOKIE;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    error::Error,
+    fmt,
+    sync::{Arc, OnceLock},
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ExtractorKind {
+    JsonPath(String),
+    Regex(String), //THE FIRST CAPTURE GROUP, OR THE WHOLE MATCH WITHOUT GROUPS
+    Header(String),
+    Cookie(String), //FROM THE SET-COOKIE HEADERS OF THE RESPONSE
+}
+
+/// Captures a value from a response into a variable of the user, to be used in later requests as `{{variable}}`.
+/// A failed extraction counts the request as failed, like a failed check.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Extractor {
+    pub variable: String,
+    pub kind: ExtractorKind,
+    #[serde(skip)]
+    regex: Arc<OnceLock<Option<Regex>>>, //COMPILED ON FIRST USE, SHARED BETWEEN USERS
+}
+
+impl Extractor {
+    pub fn new(variable: String, kind: ExtractorKind) -> Extractor {
+        Extractor {
+            variable,
+            kind,
+            regex: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn get_variable(&self) -> &String {
+        &self.variable
+    }
+
+    pub fn needs_body(&self) -> bool {
+        matches!(
+            self.kind,
+            ExtractorKind::JsonPath(_) | ExtractorKind::Regex(_)
+        )
+    }
+
+    // the regex and the json path are checked before the test starts instead of failing every request
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        match &self.kind {
+            ExtractorKind::Regex(pattern) => {
+                Regex::new(pattern).map_err(|e| {
+                    format!("extractor [{}] has an invalid regex: {}", self.variable, e)
+                })?;
+            }
+            ExtractorKind::JsonPath(path) => {
+                json_path::validate(path)
+                    .map_err(|e| format!("extractor [{}]: {}", self.variable, e))?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn get_regex(&self, pattern: &str) -> Option<&Regex> {
+        self.regex.get_or_init(|| Regex::new(pattern).ok()).as_ref()
+    }
+
+    // returns the value of the variable, or the reason if nothing could be extracted
+    pub fn extract(&self, response: &CheckResponse) -> Result<String, String> {
+        let body = response.body.unwrap_or("");
+        match &self.kind {
+            ExtractorKind::JsonPath(path) => {
+                let json: Value = serde_json::from_str(body)
+                    .map_err(|e| format!("body is not valid json: {}", e))?;
+                json_path::select_as_string(&json, path)
+                    .ok_or_else(|| format!("[{}] not found", path))
+            }
+            ExtractorKind::Regex(pattern) => {
+                let regex = self
+                    .get_regex(pattern)
+                    .ok_or_else(|| format!("invalid regex [{}]", pattern))?;
+                let captures = regex
+                    .captures(body)
+                    .ok_or_else(|| format!("body does not match [{}]", pattern))?;
+                let value = captures.get(1).or_else(|| captures.get(0));
+                Ok(value.map(|m| m.as_str().to_string()).unwrap_or_default())
+            }
+            ExtractorKind::Header(name) => response
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+                .ok_or_else(|| format!("header [{}] not found", name)),
+            ExtractorKind::Cookie(name) => response
+                .headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .find_map(|cookie| {
+                    let pair = cookie.split(';').next()?;
+                    let (key, value) = pair.split_once('=')?;
+                    (key.trim() == name).then(|| value.trim().to_string())
+                })
+                .ok_or_else(|| format!("cookie [{}] not found", name)),
+        }
+    }
+}
+
+impl fmt::Display for Extractor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "extract {}", self.variable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    fn extract(extractor: &Extractor, headers: &HeaderMap, body: &str) -> Result<String, String> {
+        extractor.extract(&CheckResponse {
+            status_code: 200,
+            headers,
+            body: Some(body),
+            response_time: Default::default(),
+        })
+    }
+
+    #[test]
+    fn invalid_regexes_and_json_paths_are_rejected() {
+        let regex = Extractor::new(String::from("id"), ExtractorKind::Regex(String::from("[")));
+        assert!(regex.validate().is_err());
+        let path = Extractor::new(
+            String::from("id"),
+            ExtractorKind::JsonPath(String::from("items[x]")),
+        );
+        assert!(path.validate().is_err());
+    }
+
+    #[test]
+    fn extracts_from_the_body_and_the_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, HeaderValue::from_static("theme=dark; Path=/"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("sid=abc; HttpOnly"));
+        let body = r#"{"token": "t1", "items": [{"id": 7}]}"#;
+        let path = Extractor::new(
+            String::from("id"),
+            ExtractorKind::JsonPath(String::from("items[0].id")),
+        );
+        assert_eq!(extract(&path, &headers, body), Ok(String::from("7")));
+        let group = Extractor::new(
+            String::from("token"),
+            ExtractorKind::Regex(String::from(r#""token": "(\w+)""#)),
+        );
+        assert_eq!(extract(&group, &headers, body), Ok(String::from("t1")));
+        let cookie = Extractor::new(
+            String::from("sid"),
+            ExtractorKind::Cookie(String::from("sid")),
+        );
+        assert_eq!(extract(&cookie, &headers, body), Ok(String::from("abc")));
+        let missing = Extractor::new(String::from("x"), ExtractorKind::Header(String::from("x")));
+        assert!(extract(&missing, &headers, body).is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::{
+    de::{MapAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::Value;
+use std::{collections::HashMap, error::Error, fmt, sync::Arc};
+use tokio::fs;
+
+pub type Row = HashMap<String, String>;
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FeederStrategy {
+    Sequential, //EVERY ROW ONCE, IN ORDER. USERS STOP WHEN THE FEEDER RUNS OUT OF ROWS
+    Random,
+    Circular, //IN ORDER, STARTING OVER AFTER THE LAST ROW
+    Unique,   //ONE ROW PER USER, KEPT FOR THE WHOLE TEST
+}
+
+impl FeederStrategy {
+    // rows of these strategies are used once, so they are split between the workers
+    pub fn is_partitioned(&self) -> bool {
+        matches!(self, FeederStrategy::Sequential | FeederStrategy::Unique)
+    }
+}
+
+impl fmt::Display for FeederStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeederStrategy::Sequential => write!(f, "SEQUENTIAL"),
+            FeederStrategy::Random => write!(f, "RANDOM"),
+            FeederStrategy::Circular => write!(f, "CIRCULAR"),
+            FeederStrategy::Unique => write!(f, "UNIQUE"),
+        }
+    }
+}
+
+/// Test data handed out to the users. The columns of a row are available to the requests as `{{column}}`.
+/// The rows are part of the test, so the workers get them from the master and need no access to the files.
+#[derive(Clone, Debug)]
+pub struct Feeder {
+    pub name: String,
+    pub strategy: FeederStrategy,
+    pub rows: Arc<Vec<Row>>,
+    position: Arc<Mutex<usize>>, //NEXT ROW, SHARED BY ALL USERS OF A TEST
+}
+
+impl Feeder {
+    pub fn new(name: String, strategy: FeederStrategy, rows: Vec<Row>) -> Feeder {
+        Feeder {
+            name,
+            strategy,
+            rows: Arc::new(rows),
+            position: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    // the first line holds the column names
+    pub fn from_csv(
+        name: String,
+        strategy: FeederStrategy,
+        csv: &str,
+    ) -> Result<Feeder, Box<dyn Error>> {
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let columns = reader.headers()?.clone();
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let row: Row = columns
+                .iter()
+                .zip(record.iter())
+                .map(|(column, value)| (column.to_string(), value.to_string()))
+                .collect();
+            rows.push(row);
+        }
+        Ok(Feeder::new(name, strategy, rows))
+    }
+
+    // an array of objects. values that are not strings are kept as json
+    pub fn from_json(
+        name: String,
+        strategy: FeederStrategy,
+        json: &str,
+    ) -> Result<Feeder, Box<dyn Error>> {
+        let objects: Vec<serde_json::Map<String, Value>> = serde_json::from_str(json)?;
+        let rows = objects
+            .into_iter()
+            .map(|object| {
+                object
+                    .into_iter()
+                    .map(|(column, value)| match value {
+                        Value::String(value) => (column, value),
+                        value => (column, value.to_string()),
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(Feeder::new(name, strategy, rows))
+    }
+
+    // the format is taken from the extension of the file, files without a json extension are read as csv
+    pub async fn from_file(
+        name: String,
+        strategy: FeederStrategy,
+        path: &str,
+    ) -> Result<Feeder, Box<dyn Error>> {
+        let content = fs::read_to_string(path).await?;
+        if path.to_lowercase().ends_with(".json") {
+            Feeder::from_json(name, strategy, &content)
+        } else {
+            Feeder::from_csv(name, strategy, &content)
+        }
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_strategy(&self) -> &FeederStrategy {
+        &self.strategy
+    }
+
+    pub fn get_rows(&self) -> &Arc<Vec<Row>> {
+        &self.rows
+    }
+
+    // none if the feeder has no rows, or a sequential or unique feeder ran out of rows
+    pub fn next_row(&self) -> Option<&Row> {
+        if self.rows.is_empty() {
+            return None;
+        }
+        match self.strategy {
+            FeederStrategy::Random => {
+                let index = rand::thread_rng().gen_range(0..self.rows.len());
+                self.rows.get(index)
+            }
+            FeederStrategy::Circular => {
+                let mut position = self.position.lock();
+                let row = self.rows.get(*position % self.rows.len());
+                *position = (*position + 1) % self.rows.len();
+                row
+            }
+            FeederStrategy::Sequential | FeederStrategy::Unique => {
+                let mut position = self.position.lock();
+                let row = self.rows.get(*position);
+                if row.is_some() {
+                    *position += 1;
+                }
+                row
+            }
+        }
+    }
+
+    // the rows of the users from offset to offset + user_count, out of total_user_count.
+    // feeders whose rows can be used more than once are kept as they are
+    pub fn partition(&self, offset: u32, user_count: u32, total_user_count: u32) -> Feeder {
+        if !self.strategy.is_partitioned() || total_user_count == 0 {
+            return self.clone();
+        }
+        let rows_count = self.rows.len() as u64;
+        let total_user_count = total_user_count as u64;
+        let start = (offset as u64 * rows_count / total_user_count) as usize;
+        let end = ((offset + user_count) as u64 * rows_count / total_user_count) as usize;
+        Feeder::new(
+            self.name.clone(),
+            self.strategy.clone(),
+            self.rows[start..end.min(self.rows.len())].to_vec(),
+        )
+    }
+}
+
+impl fmt::Display for Feeder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Feeder [{}] | Strategy [{}] | Rows [{}]",
+            self.name,
+            self.strategy,
+            self.rows.len()
+        )
+    }
+}
+
+impl Serialize for Feeder {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Feeder", 3)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("strategy", &self.strategy)?;
+        state.serialize_field("rows", &*self.rows)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Feeder {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FeederVisitor;
+
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "snake_case")]
+        enum Field {
+            Name,
+            Strategy,
+            Rows,
+        }
+        impl<'de> Visitor<'de> for FeederVisitor {
+            type Value = Feeder;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("struct Feeder")
+            }
+
+            fn visit_map<V>(self, mut map: V) -> Result<Feeder, V::Error>
+            where
+                V: MapAccess<'de>,
+            {
+                let mut name: Option<String> = None;
+                let mut strategy: Option<FeederStrategy> = None;
+                let mut rows: Option<Vec<Row>> = None;
+
+                while let Some(key) = map.next_key()? {
+                    match key {
+                        Field::Name => {
+                            if name.is_some() {
+                                return Err(serde::de::Error::duplicate_field("name"));
+                            }
+                            name = Some(map.next_value()?);
+                        }
+                        Field::Strategy => {
+                            if strategy.is_some() {
+                                return Err(serde::de::Error::duplicate_field("strategy"));
+                            }
+                            strategy = Some(map.next_value()?);
+                        }
+                        Field::Rows => {
+                            if rows.is_some() {
+                                return Err(serde::de::Error::duplicate_field("rows"));
+                            }
+                            rows = Some(map.next_value()?);
+                        }
+                    }
+                }
+                let name = name.ok_or_else(|| serde::de::Error::missing_field("name"))?;
+                let strategy =
+                    strategy.ok_or_else(|| serde::de::Error::missing_field("strategy"))?;
+                let rows = rows.ok_or_else(|| serde::de::Error::missing_field("rows"))?;
+
+                Ok(Feeder::new(name, strategy, rows))
+            }
+        }
+        const FIELDS: &[&str] = &["name", "strategy", "rows"];
+        deserializer.deserialize_struct("Feeder", FIELDS, FeederVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feeder(strategy: FeederStrategy, rows_count: usize) -> Feeder {
+        let rows = (0..rows_count)
+            .map(|index| Row::from([(String::from("index"), index.to_string())]))
+            .collect();
+        Feeder::new(String::from("feeder"), strategy, rows)
+    }
+
+    fn indices(feeder: &Feeder) -> Vec<String> {
+        feeder
+            .get_rows()
+            .iter()
+            .map(|row| row["index"].clone())
+            .collect()
+    }
+
+    // the rows of the workers, each running the given number of users
+    fn split(feeder: &Feeder, user_counts: &[u32]) -> Vec<Vec<String>> {
+        let total_user_count = user_counts.iter().sum();
+        let mut offset = 0;
+        user_counts
+            .iter()
+            .map(|user_count| {
+                let partition = feeder.partition(offset, *user_count, total_user_count);
+                offset += user_count;
+                indices(&partition)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn uneven_splits_hand_out_every_row_once() {
+        let feeder = feeder(FeederStrategy::Unique, 10);
+        let splits = split(&feeder, &[1, 1, 1]);
+        assert_eq!(
+            splits.iter().map(|rows| rows.len()).collect::<Vec<_>>(),
+            vec![3, 3, 4]
+        );
+        assert_eq!(splits.concat(), indices(&feeder));
+        let splits = split(&feeder, &[3, 1]);
+        assert_eq!(
+            splits.iter().map(|rows| rows.len()).collect::<Vec<_>>(),
+            vec![7, 3]
+        );
+        assert_eq!(splits.concat(), indices(&feeder));
+    }
+
+    #[test]
+    fn as_many_rows_as_users_give_every_worker_rows() {
+        let feeder = feeder(FeederStrategy::Sequential, 3);
+        let splits = split(&feeder, &[1, 1, 1]);
+        assert_eq!(
+            splits.iter().map(|rows| rows.len()).collect::<Vec<_>>(),
+            vec![1, 1, 1]
+        );
+        assert_eq!(splits.concat(), indices(&feeder));
+    }
+
+    #[test]
+    fn reusable_rows_are_not_split() {
+        for strategy in [FeederStrategy::Random, FeederStrategy::Circular] {
+            let feeder = feeder(strategy, 10);
+            assert_eq!(split(&feeder, &[1, 1, 1]), vec![indices(&feeder); 3]);
+        }
+        let feeder = feeder(FeederStrategy::Unique, 10);
+        assert_eq!(indices(&feeder.partition(0, 0, 0)), indices(&feeder));
+    }
+
+    #[test]
+    fn sequential_and_unique_feeders_run_out_of_rows() {
+        for strategy in [FeederStrategy::Sequential, FeederStrategy::Unique] {
+            let feeder = feeder(strategy, 2);
+            assert_eq!(
+                feeder.next_row().map(|row| row["index"].as_str()),
+                Some("0")
+            );
+            assert_eq!(
+                feeder.next_row().map(|row| row["index"].as_str()),
+                Some("1")
+            );
+            assert_eq!(feeder.next_row(), None);
+        }
+        let feeder = feeder(FeederStrategy::Circular, 2);
+        let rows: Vec<&str> = (0..3)
+            .filter_map(|_| feeder.next_row())
+            .map(|row| row["index"].as_str())
+            .collect();
+        assert_eq!(rows, vec!["0", "1", "0"]);
+    }
+}
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// values below 2^SUB_BUCKET_BITS get their own bucket, larger values are grouped
+// into SUB_BUCKET_HALF_COUNT linear sub buckets per power of two. this keeps the
+// relative error of a recorded value below 1 / SUB_BUCKET_HALF_COUNT (~1.6%).
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKET_COUNT: u64 = 1 << SUB_BUCKET_BITS;
+const SUB_BUCKET_HALF_COUNT: u64 = SUB_BUCKET_COUNT / 2;
+// a single response records at most this many missing values, the largest ones,
+// so a hung request can not outweigh every other request of the test
+const MAX_MISSING_VALUES: u64 = 1 << 20;
+
+/// A compact log-bucketed histogram.
+/// Only non empty buckets are stored, so it can be sent over the wire and merged exactly.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Histogram {
+    total_count: u64,
+    buckets: BTreeMap<u32, u64>,
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Histogram {
+            total_count: 0,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    fn bucket_index(value: u64) -> u32 {
+        if value < SUB_BUCKET_COUNT {
+            return value as u32;
+        }
+        let msb = 63 - value.leading_zeros();
+        let shift = msb - (SUB_BUCKET_BITS - 1);
+        let sub_bucket = (value >> shift) - SUB_BUCKET_HALF_COUNT;
+        (SUB_BUCKET_COUNT + (shift as u64 - 1) * SUB_BUCKET_HALF_COUNT + sub_bucket) as u32
+    }
+
+    // lowest and highest value that fall into the bucket
+    fn bucket_bounds(index: u32) -> (u64, u64) {
+        let index = index as u64;
+        if index < SUB_BUCKET_COUNT {
+            return (index, index);
+        }
+        let shift = (index - SUB_BUCKET_COUNT) / SUB_BUCKET_HALF_COUNT + 1;
+        let sub_bucket = (index - SUB_BUCKET_COUNT) % SUB_BUCKET_HALF_COUNT + SUB_BUCKET_HALF_COUNT;
+        let low = sub_bucket << shift;
+        let high = low + ((1 << shift) - 1);
+        (low, high)
+    }
+
+    pub fn record(&mut self, value: u64) {
+        self.record_n(value, 1);
+    }
+
+    pub fn record_n(&mut self, value: u64, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let bucket = self
+            .buckets
+            .entry(Histogram::bucket_index(value))
+            .or_insert(0);
+        *bucket = bucket.saturating_add(count);
+        self.total_count = self.total_count.saturating_add(count);
+    }
+
+    /// Records the value and the values of the requests that would have been sent every `expected_interval`
+    /// while waiting for it (coordinated omission correction, as done by HdrHistogram).
+    pub fn record_corrected(&mut self, value: u64, expected_interval: u64) {
+        self.record(value);
+        if expected_interval == 0 {
+            return;
+        }
+        // the missing values step down by expected_interval,
+        // so all of them that fall into the same bucket are recorded at once
+        let mut missing_value = value.saturating_sub(expected_interval);
+        let mut remaining = MAX_MISSING_VALUES;
+        while missing_value >= expected_interval && remaining > 0 {
+            let (low, _) = Histogram::bucket_bounds(Histogram::bucket_index(missing_value));
+            let count = (missing_value - low.max(expected_interval)) / expected_interval + 1;
+            let count = count.min(remaining);
+            remaining -= count;
+            self.record_n(missing_value, count);
+            missing_value = missing_value.saturating_sub(count.saturating_mul(expected_interval));
+        }
+    }
+
+    /// A histogram holding only the corrected values of a single response, see `record_corrected`.
+    pub fn corrected(value: u64, expected_interval: u64) -> Histogram {
+        let mut histogram = Histogram::new();
+        histogram.record_corrected(value, expected_interval);
+        histogram
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (index, count) in other.buckets.iter() {
+            let bucket = self.buckets.entry(*index).or_insert(0);
+            *bucket = bucket.saturating_add(*count);
+        }
+        self.total_count = self.total_count.saturating_add(other.total_count);
+    }
+
+    /// Counts recorded in this histogram but not in `earlier`.
+    /// `earlier` is expected to be a previous state of the same histogram.
+    pub fn difference(&self, earlier: &Histogram) -> Histogram {
+        let mut difference = Histogram::new();
+        for (index, count) in self.buckets.iter() {
+            let earlier_count = earlier.buckets.get(index).copied().unwrap_or(0);
+            let count = count.saturating_sub(earlier_count);
+            if count > 0 {
+                difference.buckets.insert(*index, count);
+                difference.total_count += count;
+            }
+        }
+        difference
+    }
+
+    pub fn reset(&mut self) {
+        self.buckets.clear();
+        self.total_count = 0;
+    }
+
+    pub fn get_total_count(&self) -> u64 {
+        self.total_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_count == 0
+    }
+
+    /// Value at the given percentile (0.0 - 100.0).
+    /// Returns the middle of the bucket the percentile falls in, or 0 if the histogram is empty.
+    pub fn value_at_percentile(&self, percentile: f64) -> u64 {
+        if self.total_count == 0 {
+            return 0;
+        }
+        let percentile = percentile.clamp(0.0, 100.0);
+        let rank = ((percentile / 100.0) * self.total_count as f64).ceil() as u64;
+        let rank = rank.max(1);
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter() {
+            seen += count;
+            if seen >= rank {
+                let (low, high) = Histogram::bucket_bounds(*index);
+                return low + (high - low) / 2;
+            }
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_bounds_hold_the_values_of_the_bucket() {
+        let mut value = 0;
+        while value < 1 << 40 {
+            let (low, high) = Histogram::bucket_bounds(Histogram::bucket_index(value));
+            assert!(
+                low <= value && value <= high,
+                "{} not in [{}, {}]",
+                value,
+                low,
+                high
+            );
+            assert!((high - low) * SUB_BUCKET_HALF_COUNT <= low.max(1));
+            value = value * 3 / 2 + 1;
+        }
+        let (_, high) = Histogram::bucket_bounds(Histogram::bucket_index(u64::MAX));
+        assert_eq!(high, u64::MAX);
+    }
+
+    #[test]
+    fn buckets_follow_each_other_without_gaps() {
+        for index in 0..Histogram::bucket_index(1 << 20) {
+            let (_, high) = Histogram::bucket_bounds(index);
+            let (next_low, _) = Histogram::bucket_bounds(index + 1);
+            assert_eq!(high + 1, next_low);
+        }
+    }
+
+    #[test]
+    fn value_at_percentile_takes_the_value_of_the_rank() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.value_at_percentile(50.0), 0);
+        for value in 1..=100 {
+            histogram.record(value);
+        }
+        assert_eq!(histogram.value_at_percentile(0.0), 1);
+        assert_eq!(histogram.value_at_percentile(50.0), 50);
+        assert_eq!(histogram.value_at_percentile(99.0), 99);
+        assert_eq!(histogram.value_at_percentile(99.5), 100);
+        assert_eq!(histogram.value_at_percentile(150.0), 100);
+    }
+
+    #[test]
+    fn merge_and_difference_are_exact() {
+        let mut first = Histogram::new();
+        first.record_n(10, 3);
+        first.record(1_000);
+        let mut second = Histogram::new();
+        second.record(1_000);
+        second.record(100_000);
+        let earlier = first.clone();
+        first.merge(&second);
+        assert_eq!(first.get_total_count(), 6);
+        let difference = first.difference(&earlier);
+        assert_eq!(difference.get_total_count(), second.get_total_count());
+        assert_eq!(difference.buckets, second.buckets);
+    }
+
+    #[test]
+    fn record_corrected_groups_the_missing_values_by_bucket() {
+        let cases = [(0, 0), (5, 10), (10, 10), (1_000, 7), (123_456, 1_000)];
+        for (value, expected_interval) in cases {
+            let mut one_by_one = Histogram::new();
+            one_by_one.record(value);
+            if expected_interval > 0 {
+                let mut missing_value = value.saturating_sub(expected_interval);
+                while missing_value >= expected_interval {
+                    one_by_one.record(missing_value);
+                    missing_value -= expected_interval;
+                }
+            }
+            let corrected = Histogram::corrected(value, expected_interval);
+            assert_eq!(corrected.total_count, one_by_one.total_count);
+            assert_eq!(corrected.buckets, one_by_one.buckets);
+        }
+    }
+
+    #[test]
+    fn record_corrected_caps_the_missing_values() {
+        let histogram = Histogram::corrected(u64::MAX, 1);
+        assert_eq!(histogram.get_total_count(), MAX_MISSING_VALUES + 1);
+        // the largest missing values are kept
+        assert_eq!(
+            histogram.value_at_percentile(0.0),
+            histogram.value_at_percentile(100.0)
+        );
+        let mut results = Histogram::new();
+        results.record(10);
+        results.merge(&histogram);
+        results.merge(&histogram);
+        assert_eq!(results.get_total_count(), 2 * MAX_MISSING_VALUES + 3);
+    }
+
+    #[test]
+    fn counts_saturate() {
+        let mut histogram = Histogram::new();
+        histogram.record_n(10, u64::MAX);
+        histogram.record(10);
+        histogram.merge(&histogram.clone());
+        assert_eq!(histogram.get_total_count(), u64::MAX);
+        assert_eq!(histogram.buckets[&10], u64::MAX);
+    }
+}
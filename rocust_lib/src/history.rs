@@ -0,0 +1,164 @@
+use crate::{Histogram, Results};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+// one hour of history with the default update interval of 1 second
+pub const DEFAULT_HISTORY_CAPACITY: usize = 3600;
+
+/// Results of a single interval between two updates.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub elapsed: f64, //SECONDS SINCE TEST START
+    pub total_requests: u64,
+    pub total_failed_requests: u64,
+    pub requests_per_second: f64,
+    pub failed_requests_per_second: f64,
+    pub failure_rate: f64,
+    pub median_response_time: u64,
+    pub percentile_90_response_time: u64,
+    pub percentile_95_response_time: u64,
+    pub percentile_99_response_time: u64,
+    pub active_users: u32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Series {
+    snapshots: VecDeque<Snapshot>,
+    #[serde(skip)]
+    last_histogram: Histogram, //HISTOGRAM AT THE LAST SNAPSHOT, USED TO GET THE INTERVAL PERCENTILES
+}
+
+impl Series {
+    pub fn new() -> Series {
+        Series {
+            snapshots: VecDeque::new(),
+            last_histogram: Histogram::new(),
+        }
+    }
+
+    fn record(&mut self, elapsed: f64, results: &Results, active_users: u32, capacity: usize) {
+        let (last_elapsed, last_total_requests, last_total_failed_requests) =
+            match self.snapshots.back() {
+                Some(last) => (
+                    last.elapsed,
+                    last.total_requests,
+                    last.total_failed_requests,
+                ),
+                None => (0.0, 0, 0),
+            };
+        let interval = elapsed - last_elapsed;
+        let requests = results.total_requests.saturating_sub(last_total_requests);
+        let failed_requests = results
+            .total_failed_requests
+            .saturating_sub(last_total_failed_requests);
+        let (requests_per_second, failed_requests_per_second) = if interval > 0.0 {
+            (
+                requests as f64 / interval,
+                failed_requests as f64 / interval,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        let failure_rate = if requests > 0 {
+            failed_requests as f64 / requests as f64
+        } else {
+            0.0
+        };
+        let interval_histogram = results.histogram.difference(&self.last_histogram);
+        self.last_histogram = results.histogram.clone();
+
+        self.snapshots.push_back(Snapshot {
+            elapsed,
+            total_requests: results.total_requests,
+            total_failed_requests: results.total_failed_requests,
+            requests_per_second,
+            failed_requests_per_second,
+            failure_rate,
+            median_response_time: interval_histogram.value_at_percentile(50.0),
+            percentile_90_response_time: interval_histogram.value_at_percentile(90.0),
+            percentile_95_response_time: interval_histogram.value_at_percentile(95.0),
+            percentile_99_response_time: interval_histogram.value_at_percentile(99.0),
+            active_users,
+        });
+        while self.snapshots.len() > capacity {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn get_snapshots(&self) -> &VecDeque<Snapshot> {
+        &self.snapshots
+    }
+
+    pub fn get_last_snapshot(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+}
+
+/// Time series of snapshots for the aggregated results and every endpoint, scenario, step and user class.
+/// Old snapshots are dropped once `capacity` is reached.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct History {
+    capacity: usize,
+    aggregate: Series,
+    endpoints: HashMap<String, Series>, //ENDPOINT, SCENARIO, STEP OR USER CLASS ID -> SERIES
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History {
+            capacity,
+            aggregate: Series::new(),
+            endpoints: HashMap::new(),
+        }
+    }
+
+    pub fn record_aggregate(&mut self, elapsed: f64, results: &Results, active_users: u32) {
+        self.aggregate
+            .record(elapsed, results, active_users, self.capacity);
+    }
+
+    pub fn record_endpoint(
+        &mut self,
+        id: &str,
+        elapsed: f64,
+        results: &Results,
+        active_users: u32,
+    ) {
+        self.endpoints.entry(id.to_string()).or_default().record(
+            elapsed,
+            results,
+            active_users,
+            self.capacity,
+        );
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get_aggregate(&self) -> &Series {
+        &self.aggregate
+    }
+
+    pub fn get_endpoints(&self) -> &HashMap<String, Series> {
+        &self.endpoints
+    }
+
+    pub fn get_endpoint(&self, id: &str) -> Option<&Series> {
+        self.endpoints.get(id)
+    }
+
+    pub fn reset(&mut self) {
+        *self = History::new(self.capacity);
+    }
+}
@@ -0,0 +1,134 @@
+use serde_json::Value;
+use std::error::Error;
+
+// a path segment is either an object key or an array index
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+fn parse(path: &str) -> Option<Vec<Segment<'_>>> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut segments = Vec::new();
+    for part in path.split('.').filter(|part| !part.is_empty()) {
+        // "items[0][1]" -> key "items", indices 0 and 1
+        let (key, mut rest) = match part.find('[') {
+            Some(position) => part.split_at(position),
+            None => (part, ""),
+        };
+        if !key.is_empty() {
+            segments.push(Segment::Key(key));
+        }
+        while let Some(stripped) = rest.strip_prefix('[') {
+            let end = stripped.find(']')?;
+            let index = stripped[..end].trim().parse().ok()?;
+            segments.push(Segment::Index(index));
+            rest = &stripped[end + 1..];
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+    }
+    Some(segments)
+}
+
+/// Selects a value with a simple JSON path like `$.data.items[0].id`.
+/// Array elements can also be selected with a numeric key like `data.items.0.id`.
+pub fn select<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+    for segment in parse(path)? {
+        current = match segment {
+            Segment::Key(key) => match current {
+                Value::Object(map) => map.get(key)?,
+                Value::Array(array) => array.get(key.parse::<usize>().ok()?)?,
+                _ => return None,
+            },
+            Segment::Index(index) => current.as_array()?.get(index)?,
+        };
+    }
+    Some(current)
+}
+
+// a path that can not be parsed selects nothing, so it is rejected before the test starts
+pub fn validate(path: &str) -> Result<(), Box<dyn Error>> {
+    match parse(path) {
+        Some(_) => Ok(()),
+        None => Err(format!("invalid json path [{}]", path).into()),
+    }
+}
+
+/// Like [`select`] but returns strings without quotes, so they can be compared or templated.
+pub fn select_as_string(value: &Value, path: &str) -> Option<String> {
+    select(value, path).map(|selected| match selected {
+        Value::String(string) => string.clone(),
+        other => other.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document() -> Value {
+        json!({"data": {"items": [{"id": 7, "name": "seven"}, [1, 2]], "count": 2}})
+    }
+
+    #[test]
+    fn selects_keys_and_indices() {
+        let document = document();
+        assert_eq!(select(&document, "$.data.count"), Some(&json!(2)));
+        assert_eq!(select(&document, "data.items[0].id"), Some(&json!(7)));
+        assert_eq!(select(&document, "data.items.0.id"), Some(&json!(7)));
+        assert_eq!(select(&document, "data.items[1][ 1 ]"), Some(&json!(2)));
+        assert_eq!(select(&document, "$"), Some(&document));
+    }
+
+    #[test]
+    fn select_as_string_drops_the_quotes() {
+        let document = document();
+        assert_eq!(
+            select_as_string(&document, "data.items[0].name"),
+            Some(String::from("seven"))
+        );
+        assert_eq!(
+            select_as_string(&document, "data.items[1]"),
+            Some(String::from("[1,2]"))
+        );
+    }
+
+    #[test]
+    fn invalid_paths_select_nothing() {
+        let document = document();
+        for path in [
+            "data.items[0",      //UNCLOSED BRACKET
+            "data.items[first]", //NOT AN INDEX
+            "data.items[-1]",
+            "data.items[0]id", //TEXT AFTER THE BRACKET
+        ] {
+            assert_eq!(select(&document, path), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn missing_values_select_nothing() {
+        let document = document();
+        for path in [
+            "data.missing",
+            "data.items[2]",
+            "data.items.first",
+            "data.count.value", //KEY OF A NUMBER
+            "data[0]",          //INDEX OF AN OBJECT
+        ] {
+            assert_eq!(select(&document, path), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn invalid_paths_are_rejected_by_validate() {
+        assert!(validate("data.items[0].id").is_ok());
+        assert!(validate("$").is_ok());
+        assert!(validate("data.items[0").is_err());
+        assert!(validate("data.items[first]").is_err());
+    }
+}
@@ -2,10 +2,22 @@ pub mod traits;
 pub use traits::HasResults;
 pub use traits::Runnable;
 
+pub mod histogram;
+pub use histogram::Histogram;
+
+pub mod history;
+pub use history::History;
+
+pub mod errors;
+pub use errors::ErrorBreakdown;
+
 pub mod results;
 pub use results::Results;
 pub use results::SentResults;
 
+pub mod threshold;
+pub use threshold::Threshold;
+
 pub mod status;
 pub use status::Status;
 
@@ -13,10 +25,50 @@ pub mod logger;
 pub use logger::LogType;
 pub use logger::Logger;
 
+pub mod json_path;
+
+pub mod check;
+pub use check::Check;
+
+pub mod extractor;
+pub use extractor::Extractor;
+
+pub mod template;
+
+pub mod think_time;
+pub use think_time::ThinkTime;
+
+pub mod feeder;
+pub use feeder::Feeder;
+
+pub mod shape;
+pub use shape::LoadShape;
+
+pub mod arrival;
+pub use arrival::ArrivalRate;
+
+pub mod auth;
+pub use auth::Auth;
+
+pub mod cookies;
+pub use cookies::CookieConfig;
+
+pub mod body;
+pub use body::Body;
+
+pub mod client;
+pub use client::ClientConfig;
+
 pub mod endpoint;
 pub use endpoint::EndPoint;
 pub use endpoint::Method;
 
+pub mod scenario;
+pub use scenario::Scenario;
+
+pub mod user_class;
+pub use user_class::UserClass;
+
 pub mod master;
 pub use master::Master;
 
@@ -25,3 +77,5 @@ pub use test::Test;
 
 pub mod worker;
 pub use worker::Worker;
+
+pub mod report;
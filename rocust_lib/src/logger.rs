@@ -9,7 +9,7 @@ use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
 };
 use std::{error::Error, fmt, sync::Arc};
-use tokio::{fs, fs::OpenOptions, io::AsyncWriteExt};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
 
 pub enum LogType {
     Info,
@@ -53,7 +53,7 @@ impl Logger {
         self.print_to_console = print_to_console;
     }
 
-    fn get_date_and_time(&self) -> DelayedFormat<StrftimeItems> {
+    fn get_date_and_time(&self) -> DelayedFormat<StrftimeItems<'_>> {
         let now: DateTime<Utc> = Utc::now();
         now.format("%Y.%m.%d %H:%M:%S")
     }
@@ -80,7 +80,7 @@ impl Logger {
             let mut buffer = self.buffer.write();
             for message in buffer.iter() {
                 result.push_str(message);
-                result.push_str("\n");
+                result.push('\n');
             }
             buffer.clear();
         }
@@ -178,7 +178,7 @@ impl<'de> Deserialize<'de> for Logger {
                 })
             }
         }
-        const FIELDS: &'static [&'static str] = &["logfile_path"];
-        deserializer.deserialize_struct("Logger", &FIELDS, LoggerVisitor)
+        const FIELDS: &[&str] = &["logfile_path"];
+        deserializer.deserialize_struct("Logger", FIELDS, LoggerVisitor)
     }
 }
@@ -1,4 +1,6 @@
-use crate::{HasResults, LogType, Logger, Runnable, SentResults, Status, Test};
+use crate::{
+    report::html, HasResults, History, LogType, Logger, Runnable, SentResults, Status, Test,
+};
 use async_trait::async_trait;
 use futures_util::{SinkExt, StreamExt};
 use parking_lot::RwLock;
@@ -38,6 +40,7 @@ pub enum ControlWebSocketMessage {
     Create(Test),
     Start,
     Stop,
+    RampDown(u64), //SECONDS TO STOP THE USERS IN BEFORE THE TEST FINISHES
     Finish,
     Update(ResultsWebsocketMessage),
 }
@@ -64,6 +67,7 @@ impl fmt::Display for ControlWebSocketMessage {
             }
             ControlWebSocketMessage::Start => write!(f, "Start"),
             ControlWebSocketMessage::Stop => write!(f, "Stop"),
+            ControlWebSocketMessage::RampDown(ramp_down) => write!(f, "RampDown({})", ramp_down),
             ControlWebSocketMessage::Finish => write!(f, "Finish"),
             ControlWebSocketMessage::Update(_) => write!(f, "Update"),
         }
@@ -74,19 +78,55 @@ impl fmt::Display for ControlWebSocketMessage {
 pub struct ResultsWebsocketMessage {
     agg_sent_results: SentResults,
     endpoints_sent_results: HashMap<String, SentResults>,
+    scenarios_sent_results: HashMap<String, SentResults>, //SCENARIO NAME -> TRANSACTION RESULTS
+    steps_sent_results: HashMap<String, SentResults>, //STEP ID -> STEP RESULTS
+    user_classes_sent_results: HashMap<String, SentResults>, //CLASS NAME -> RESULTS OF ALL USERS OF THE CLASS
     //TODO: users_sent_results: HashMap<String, SentResults>,
+    active_users: u32,
 }
 
 impl ResultsWebsocketMessage {
     pub fn new(
         agg_sent_results: SentResults,
         endpoints_sent_results: HashMap<String, SentResults>,
+        scenarios_sent_results: HashMap<String, SentResults>,
+        steps_sent_results: HashMap<String, SentResults>,
+        user_classes_sent_results: HashMap<String, SentResults>,
+        active_users: u32,
     ) -> Self {
         Self {
             agg_sent_results,
             endpoints_sent_results,
+            scenarios_sent_results,
+            steps_sent_results,
+            user_classes_sent_results,
+            active_users,
         }
     }
+
+    pub fn get_agg_sent_results(&self) -> &SentResults {
+        &self.agg_sent_results
+    }
+
+    pub fn get_endpoints_sent_results(&self) -> &HashMap<String, SentResults> {
+        &self.endpoints_sent_results
+    }
+
+    pub fn get_scenarios_sent_results(&self) -> &HashMap<String, SentResults> {
+        &self.scenarios_sent_results
+    }
+
+    pub fn get_steps_sent_results(&self) -> &HashMap<String, SentResults> {
+        &self.steps_sent_results
+    }
+
+    pub fn get_user_classes_sent_results(&self) -> &HashMap<String, SentResults> {
+        &self.user_classes_sent_results
+    }
+
+    pub fn get_active_users(&self) -> u32 {
+        self.active_users
+    }
 }
 
 #[derive(Debug)]
@@ -145,9 +185,33 @@ impl State {
         }
         let new_remainning_users_count = remaining_users_count - user_count;
         if new_remainning_users_count < user_count {
-            user_count = user_count + new_remainning_users_count;
+            user_count += new_remainning_users_count;
         }
         self.set_remaining_users_count(remaining_users_count - user_count);
+        //the worker gets the rows of its users only, and its share of the spawn rate, the load shape, the arrival rate
+        //and the users of every user class
+        let total_user_count = test.get_user_count();
+        if let Some(spawn_rate) = *test.get_spawn_rate() {
+            test.set_spawn_rate(Some(
+                spawn_rate * user_count as f64 / total_user_count.max(1) as f64,
+            ));
+        }
+        test.partition_feeders(
+            total_user_count - remaining_users_count,
+            user_count,
+            total_user_count,
+        );
+        test.partition_load_shape(
+            total_user_count - remaining_users_count,
+            user_count,
+            total_user_count,
+        );
+        test.partition_arrival_rate(
+            total_user_count - remaining_users_count,
+            user_count,
+            total_user_count,
+        );
+        test.partition_user_classes(total_user_count - remaining_users_count, user_count);
         test.set_user_count(user_count);
         user_count
     }
@@ -171,28 +235,112 @@ impl State {
             .insert(worker_id.to_string(), results);
     }
 
+    fn get_active_users_count(&self) -> u32 {
+        self.workers_results
+            .read()
+            .values()
+            .map(|results_websocket_message| results_websocket_message.active_users)
+            .sum()
+    }
+
     fn combine_results(&self) {
         //reset results
         self.test.get_results().write().reset();
-        for endpint in self.test.get_endpoints().iter() {
+        for endpint in self.test.get_all_endpoints() {
             endpint.get_results().write().reset();
         }
+        for scenario in self.test.get_scenarios().iter() {
+            scenario.get_results().write().reset();
+        }
+        for user_class in self.test.get_user_classes().iter() {
+            user_class.get_results().write().reset();
+            for scenario in user_class.get_scenarios().iter() {
+                scenario.get_results().write().reset();
+            }
+        }
         for (_, results_websocket_message) in self.workers_results.read().iter() {
             //combine agg results
             let agg_results = self.test.get_results();
             let agg_sent_results = &results_websocket_message.agg_sent_results;
-            agg_results.write().combine_sent_results(&agg_sent_results);
+            agg_results.write().combine_sent_results(agg_sent_results);
             //combine endpoint results
             let endpoints = self.test.get_endpoints();
             let endpoints_sent_results = &results_websocket_message.endpoints_sent_results;
             for endpoint in endpoints.iter() {
                 let endpoint_results = endpoint.get_results();
-                if let Some(endpoint_sent_results) = endpoints_sent_results.get(&endpoint.url) {
+                if let Some(endpoint_sent_results) = endpoints_sent_results.get(&endpoint.get_id()) {
                     endpoint_results
                         .write()
                         .combine_sent_results(endpoint_sent_results);
                 }
             }
+            //combine scenario and step results
+            for scenario in self.test.get_scenarios().iter() {
+                if let Some(scenario_sent_results) = results_websocket_message
+                    .scenarios_sent_results
+                    .get(scenario.get_name())
+                {
+                    scenario
+                        .get_results()
+                        .write()
+                        .combine_sent_results(scenario_sent_results);
+                }
+                for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                    if let Some(step_sent_results) = results_websocket_message
+                        .steps_sent_results
+                        .get(&scenario.get_step_id(index))
+                    {
+                        endpoint
+                            .get_results()
+                            .write()
+                            .combine_sent_results(step_sent_results);
+                    }
+                }
+            }
+            //combine user class results, their endpoints, scenarios and steps are sent under ids with the class name
+            for user_class in self.test.get_user_classes().iter() {
+                if let Some(user_class_sent_results) = results_websocket_message
+                    .user_classes_sent_results
+                    .get(user_class.get_name())
+                {
+                    user_class
+                        .get_results()
+                        .write()
+                        .combine_sent_results(user_class_sent_results);
+                }
+                for endpoint in user_class.get_endpoints().iter() {
+                    if let Some(endpoint_sent_results) =
+                        endpoints_sent_results.get(&user_class.get_endpoint_id(endpoint))
+                    {
+                        endpoint
+                            .get_results()
+                            .write()
+                            .combine_sent_results(endpoint_sent_results);
+                    }
+                }
+                for scenario in user_class.get_scenarios().iter() {
+                    if let Some(scenario_sent_results) = results_websocket_message
+                        .scenarios_sent_results
+                        .get(&user_class.get_scenario_id(scenario))
+                    {
+                        scenario
+                            .get_results()
+                            .write()
+                            .combine_sent_results(scenario_sent_results);
+                    }
+                    for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                        if let Some(step_sent_results) = results_websocket_message
+                            .steps_sent_results
+                            .get(&user_class.get_step_id(scenario, index))
+                        {
+                            endpoint
+                                .get_results()
+                                .write()
+                                .combine_sent_results(step_sent_results);
+                        }
+                    }
+                }
+            }
             //TODO: combine user results
         }
         //calculate requests per second
@@ -202,7 +350,11 @@ impl State {
         ) {
             self.test.calculate_requests_per_second(&elapsed);
             self.test.calculate_failed_requests_per_second(&elapsed);
+            self.test
+                .calculate_current_requests_per_second(&elapsed, &self.test.get_current_window());
         }
+        //calculate percentiles from the merged histograms
+        self.test.calculate_percentiles();
     }
 }
 
@@ -296,18 +448,39 @@ impl Master {
             .await
     }
 
+    fn ramp_down(&self, ramp_down: u64) {
+        self.state.logger.log_buffered(
+            LogType::Info,
+            &format!("Ramping down in {} seconds", ramp_down),
+        );
+        let message = ControlWebSocketMessage::RampDown(ramp_down);
+        if let Some(json) = message.into_json() {
+            if self.state.broadcast_tx.send(json).is_err() {
+                self.state
+                    .logger
+                    .log_buffered(LogType::Error, "Error sending message to worker");
+            }
+        }
+    }
+
     fn set_up_run_message(&self) {
         let master_handle = self.clone();
         let mut run_message = String::from("Test running forever, press ctrl+c to stop");
-        if let Some(run_time) = master_handle.state.test.get_run_time().clone() {
+        if let Some(run_time) = master_handle.state.test.get_effective_run_time() {
             run_message = format!("Test running for {} seconds", run_time);
+            let ramp_down = *master_handle.state.test.get_ramp_down();
             tokio::spawn(async move {
                 tokio::time::sleep(Duration::from_secs(run_time)).await;
+                //the workers stop their users one after another, the master finishes at the end of the ramp down
+                if let Some(ramp_down) = ramp_down {
+                    master_handle.ramp_down(ramp_down);
+                    tokio::time::sleep(Duration::from_secs(ramp_down)).await;
+                }
                 master_handle.finish();
                 master_handle
                     .state
                     .logger
-                    .log_buffered(LogType::Info, &format!("Master finished"));
+                    .log_buffered(LogType::Info, "Master finished");
             });
         }
         self.state.logger.log_buffered(LogType::Info, &run_message);
@@ -345,10 +518,9 @@ impl Master {
         let mpsc_rx = self.mpsc_rx.write().take();
         tokio::spawn(async move {
             if let Some(mut mpsc_rx) = mpsc_rx {
-                while let Some(_) = mpsc_rx.recv().await {
+                if mpsc_rx.recv().await.is_some() {
                     master_handle.set_up_run_message();
                     master_handle.setup_update_in_background();
-                    break;
                 }
             }
         });
@@ -374,10 +546,19 @@ impl Master {
         loop {
             // combime workers results
             self.combine_results();
+            self.record_history();
+            //abort on breached thresholds
+            if self.state.test.should_abort() {
+                self.finish();
+            }
 
             //print stats
             if *self.print_stats_to_console {
+                self.state
+                    .test
+                    .print_users(self.state.get_active_users_count());
                 self.state.test.print_stats();
+                self.state.test.print_errors();
             }
             //log
             let _ = self.state.logger.flush_buffer().await;
@@ -393,14 +574,72 @@ impl Master {
         self.state.combine_results();
     }
 
+    pub fn record_history(&self) {
+        self.state
+            .test
+            .record_history(self.state.get_active_users_count());
+    }
+
+    pub fn get_history(&self) -> Arc<RwLock<History>> {
+        self.state.test.get_history().clone()
+    }
+
     pub fn get_workers_results(&self) -> HashMap<String, ResultsWebsocketMessage> {
         self.state.workers_results.read().clone()
     }
+
+    // a stopped master or a master with an error keeps its status
+    fn set_verdict(&self) {
+        if !matches!(self.get_status(), Status::Finished) {
+            return;
+        }
+        if let Some(message) = self.state.test.get_verdict() {
+            self.state.logger.log_buffered(
+                LogType::Warning,
+                &format!("Test failed, thresholds breached: {}", message),
+            );
+            self.set_status(Status::Failed(message));
+        }
+    }
+
+    async fn write_html_report(&self) {
+        if let Some(path) = self.state.test.get_html_report_path() {
+            let workers_results = self.get_workers_results();
+            match html::write_html(&self.state.test, Some(&workers_results), path).await {
+                Ok(_) => {
+                    self.state.logger.log_buffered(
+                        LogType::Info,
+                        &format!("HTML report written to [{}]", path),
+                    );
+                }
+                Err(e) => {
+                    self.state.logger.log_buffered(
+                        LogType::Error,
+                        &format!("Error while writing HTML report: {}", e),
+                    );
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
 impl Runnable for Master {
     async fn run(&mut self) {
+        //the workers run without thresholds, so the master validates the test before sending it
+        if let Some(message) = self
+            .state
+            .test
+            .validate()
+            .and_then(|_| self.state.test.validate_feeder_partitions())
+            .err()
+            .map(|e| format!("Invalid test: {}", e))
+        {
+            self.state.logger.log_buffered(LogType::Error, &message);
+            self.set_status(Status::Error(message));
+            let _ = self.state.logger.flush_buffer().await;
+            return;
+        }
         self.set_status(Status::Running);
         self.run_background_tasks_on_test_start();
         let token = self.token.lock().unwrap().clone();
@@ -419,6 +658,9 @@ impl Runnable for Master {
         }
         self.join_handles().await;
         self.combine_results();
+        self.set_verdict();
+        self.write_html_report().await;
+        self.state.test.write_reports(&self.state.logger).await;
         self.state
             .logger
             .log_buffered(LogType::Info, "Terminating... Bye!");
@@ -435,7 +677,7 @@ impl Runnable for Master {
             if self.state.broadcast_tx.send(json).is_err() {
                 self.state
                     .logger
-                    .log_buffered(LogType::Error, &format!("Error sending message to worker"));
+                    .log_buffered(LogType::Error, "Error sending message to worker");
             }
         }
     }
@@ -449,7 +691,7 @@ impl Runnable for Master {
             if self.state.broadcast_tx.send(json).is_err() {
                 self.state
                     .logger
-                    .log_buffered(LogType::Error, &format!("Error sending message to worker"));
+                    .log_buffered(LogType::Error, "Error sending message to worker");
             }
         }
     }
@@ -511,11 +753,8 @@ fn ws(ws: WebSocket, state: Data<&Arc<State>>) -> impl IntoResponse {
             while let Some(Ok(msg)) = stream.next().await {
                 if let Message::Text(text) = msg {
                     if let Ok(ws_message) = ControlWebSocketMessage::from_json(&text) {
-                        match ws_message {
-                            ControlWebSocketMessage::Update(results_websocket_message) => {
-                                state.update_workers_resluts(&worker_id, results_websocket_message);
-                            }
-                            _ => {}
+                        if let ControlWebSocketMessage::Update(results_websocket_message) = ws_message {
+                            state.update_workers_resluts(&worker_id, results_websocket_message);
                         }
                     } else {
                         state
@@ -552,3 +791,127 @@ fn ws(ws: WebSocket, state: Data<&Arc<State>>) -> impl IntoResponse {
         });
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        feeder::{FeederStrategy, Row},
+        threshold::{Comparison, Metric},
+        EndPoint, Feeder, Threshold,
+    };
+
+    #[test]
+    fn workers_share_the_spawn_rate_by_their_users() {
+        let mut test = Test::new(
+            String::from("spawn rate"),
+            10,
+            None,
+            (0, 0),
+            String::from("http://127.0.0.1"),
+            vec![EndPoint::new_get(String::from("/"), None, None)],
+            None,
+            String::new(),
+            false,
+            false,
+        );
+        test.set_spawn_rate(Some(5.0));
+        let master = Master::new(
+            String::from("Master"),
+            3,
+            test,
+            String::from("127.0.0.1:0"),
+            String::new(),
+            false,
+            false,
+        );
+        let mut user_counts = Vec::new();
+        let mut spawn_rates = Vec::new();
+        for _ in 0..3 {
+            let mut test = master.state.test.clone();
+            user_counts.push(master.state.set_test_workers_count(&mut test));
+            spawn_rates.push(test.get_spawn_rate().unwrap());
+        }
+        assert_eq!(user_counts, vec![3, 3, 4]);
+        assert_eq!(spawn_rates, vec![1.5, 1.5, 2.0]);
+    }
+
+    #[test]
+    fn every_worker_gets_rows_of_a_sequential_feeder() {
+        let rows: Vec<Row> = (0..4)
+            .map(|index| Row::from([(String::from("index"), index.to_string())]))
+            .collect();
+        let mut test = Test::new(
+            String::from("feeder"),
+            3,
+            None,
+            (0, 0),
+            String::from("http://127.0.0.1"),
+            vec![EndPoint::new_get(String::from("/"), None, None)],
+            None,
+            String::new(),
+            false,
+            false,
+        );
+        test.set_feeders(vec![Feeder::new(
+            String::from("rows"),
+            FeederStrategy::Sequential,
+            rows[..2].to_vec(),
+        )]);
+        assert!(test.validate().is_ok());
+        assert!(test.validate_feeder_partitions().is_err());
+        test.set_feeders(vec![Feeder::new(
+            String::from("rows"),
+            FeederStrategy::Sequential,
+            rows,
+        )]);
+        assert!(test.validate_feeder_partitions().is_ok());
+        let master = Master::new(
+            String::from("Master"),
+            3,
+            test,
+            String::from("127.0.0.1:0"),
+            String::new(),
+            false,
+            false,
+        );
+        for _ in 0..3 {
+            let mut test = master.state.test.clone();
+            master.state.set_test_workers_count(&mut test);
+            assert!(test.validate().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_tests_are_not_sent_to_the_workers() {
+        let mut test = Test::new(
+            String::from("invalid"),
+            1,
+            None,
+            (0, 0),
+            String::from("http://127.0.0.1"),
+            vec![EndPoint::new_get(String::from("/"), None, None)],
+            None,
+            String::new(),
+            false,
+            false,
+        );
+        let mut threshold = Threshold::new(Metric::FailureRate, Comparison::LessThan, 0.01);
+        threshold.set_endpoint(Some(String::from("GET /typo")));
+        test.set_thresholds(vec![threshold]);
+        let mut master = Master::new(
+            String::from("Master"),
+            1,
+            test,
+            String::from("127.0.0.1:0"),
+            String::new(),
+            false,
+            false,
+        );
+        master.run().await;
+        match master.get_status() {
+            Status::Error(message) => assert!(message.contains("GET /typo"), "{}", message),
+            status => panic!("unexpected status {}", status),
+        }
+    }
+}
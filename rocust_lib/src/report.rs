@@ -0,0 +1,274 @@
+use crate::{
+    history::{Series, Snapshot},
+    results::{format_optional_response_time, format_response_time},
+    HasResults, Results, Runnable, Test,
+};
+use serde::Serialize;
+use std::error::Error;
+use tokio::fs;
+
+pub mod html;
+
+const CSV_HEADER: &str = "Type,Name,Total Requests,Failed Requests,Connection Errors,Requests/s,Failed Requests/s,Average Response Time (ms),Min Response Time (ms),Median Response Time (ms),P90 Response Time (ms),P95 Response Time (ms),P99 Response Time (ms),P99.9 Response Time (ms),Max Response Time (ms)";
+
+fn escape_csv(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub(crate) fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn create_csv_row(row_type: &str, name: &str, results: &Results) -> String {
+    [
+        escape_csv(row_type),
+        escape_csv(name),
+        results.total_requests.to_string(),
+        results.total_failed_requests.to_string(),
+        results.total_connection_errors.to_string(),
+        results.requests_per_second.to_string(),
+        results.failed_requests_per_second.to_string(),
+        format_response_time(results.average_response_time),
+        format_optional_response_time(results.min_response_time),
+        format_response_time(results.median_response_time),
+        format_response_time(results.percentile_90_response_time),
+        format_response_time(results.percentile_95_response_time),
+        format_response_time(results.percentile_99_response_time),
+        format_response_time(results.percentile_999_response_time),
+        format_optional_response_time(results.max_response_time),
+    ]
+    .join(",")
+}
+
+/// Aggregated, per endpoint, scenario, step, user class and per user results as CSV.
+pub fn create_csv(test: &Test) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    csv.push_str(&create_csv_row(
+        "Aggregated",
+        "",
+        &test.get_results().read(),
+    ));
+    csv.push('\n');
+    for (kind, id, results) in test.get_all_results() {
+        csv.push_str(&create_csv_row(kind, &id, &results.read()));
+        csv.push('\n');
+    }
+    for user in test.get_users().read().iter() {
+        csv.push_str(&create_csv_row(
+            "User",
+            user.get_id(),
+            &user.get_results().read(),
+        ));
+        csv.push('\n');
+    }
+    csv
+}
+
+#[derive(Serialize)]
+struct HistoryLine<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    snapshot: &'a Snapshot,
+}
+
+fn push_history_lines(
+    jsonl: &mut String,
+    name: &str,
+    series: &Series,
+) -> Result<(), Box<dyn Error>> {
+    for snapshot in series.get_snapshots().iter() {
+        let line = HistoryLine { name, snapshot };
+        jsonl.push_str(&serde_json::to_string(&line)?);
+        jsonl.push('\n');
+    }
+    Ok(())
+}
+
+/// The results history as JSON Lines, one snapshot per line.
+/// The aggregated snapshots are named `Aggregated`, the other snapshots by the id of their endpoint, scenario,
+/// step or user class.
+pub fn create_history_jsonl(test: &Test) -> Result<String, Box<dyn Error>> {
+    let history = test.get_history().read();
+    let mut jsonl = String::new();
+    push_history_lines(&mut jsonl, "Aggregated", history.get_aggregate())?;
+    for (_, id, _) in test.get_all_results() {
+        if let Some(series) = history.get_endpoint(&id) {
+            push_history_lines(&mut jsonl, &id, series)?;
+        }
+    }
+    Ok(jsonl)
+}
+
+// with thresholds a testcase fails if one of its thresholds is breached,
+// without thresholds it fails if it had any failed requests or connection errors
+fn create_failure_message(test: &Test, id: Option<&String>, results: &Results) -> Option<String> {
+    if !test.get_thresholds().is_empty() {
+        let breaches: Vec<String> = test
+            .get_thresholds()
+            .iter()
+            .filter(|threshold| threshold.get_endpoint() == id)
+            .filter_map(|threshold| threshold.evaluate(results))
+            .map(|breach| breach.to_string())
+            .collect();
+        if breaches.is_empty() {
+            return None;
+        }
+        return Some(format!("Thresholds breached: {}", breaches.join(", ")));
+    }
+    if results.total_failed_requests == 0 && results.total_connection_errors == 0 {
+        return None;
+    }
+    Some(format!(
+        "{} failed requests and {} connection errors out of {} requests",
+        results.total_failed_requests, results.total_connection_errors, results.total_requests
+    ))
+}
+
+// the results are collected over the whole run, so the testcases have no time of their own
+fn create_testcase(
+    test: &Test,
+    name: &str,
+    id: Option<&String>,
+    results: &Results,
+    failures: &mut u32,
+) -> String {
+    let mut testcase = format!(
+        "    <testcase classname=\"{}\" name=\"{}\">\n",
+        escape_xml(test.get_id()),
+        escape_xml(name)
+    );
+    if let Some(message) = create_failure_message(test, id, results) {
+        *failures += 1;
+        testcase.push_str(&format!(
+            "      <failure message=\"{}\">{}</failure>\n",
+            escape_xml(&message),
+            escape_xml(&results.to_string())
+        ));
+    }
+    testcase.push_str("    </testcase>\n");
+    testcase
+}
+
+/// A JUnit XML report with one testcase per endpoint, scenario, step and user class.
+/// Thresholds on the aggregated results get an additional `Aggregated` testcase.
+pub fn create_junit_xml(test: &Test) -> String {
+    let elapsed = test
+        .get_elapsed_time()
+        .map(|elapsed| elapsed.as_secs_f64())
+        .unwrap_or(0.0);
+    let mut testcases = String::new();
+    let mut tests = 0;
+    let mut failures = 0;
+    for (_, id, results) in test.get_all_results() {
+        testcases.push_str(&create_testcase(
+            test,
+            &id,
+            Some(&id),
+            &results.read(),
+            &mut failures,
+        ));
+        tests += 1;
+    }
+    if test
+        .get_thresholds()
+        .iter()
+        .any(|threshold| threshold.get_endpoint().is_none())
+    {
+        testcases.push_str(&create_testcase(
+            test,
+            "Aggregated",
+            None,
+            &test.get_results().read(),
+            &mut failures,
+        ));
+        tests += 1;
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{}\">\n{}  </testsuite>\n</testsuites>\n",
+        escape_xml(test.get_id()),
+        tests,
+        failures,
+        elapsed,
+        testcases
+    )
+}
+
+pub async fn write_csv(test: &Test, path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, create_csv(test)).await?;
+    Ok(())
+}
+
+pub async fn write_history_jsonl(test: &Test, path: &str) -> Result<(), Box<dyn Error>> {
+    let jsonl = create_history_jsonl(test)?;
+    fs::write(path, jsonl).await?;
+    Ok(())
+}
+
+pub async fn write_junit_xml(test: &Test, path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, create_junit_xml(test)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EndPoint, Logger};
+
+    fn create_test() -> Test {
+        Test::new(
+            String::from("report"),
+            1,
+            None,
+            (0, 0),
+            String::from("http://127.0.0.1"),
+            vec![
+                EndPoint::new_get(String::from("/"), None, None),
+                EndPoint::new_post(String::from("/login"), None, None),
+            ],
+            None,
+            String::new(),
+            false,
+            false,
+        )
+    }
+
+    #[test]
+    fn junit_testcases_have_no_time_of_their_own() {
+        let test = create_test();
+        test.get_endpoints()[1].get_results().write().add_failed(500);
+        let junit = create_junit_xml(&test);
+        assert!(junit.contains("<testcase classname=\"report\" name=\"GET /\">"));
+        assert!(junit.contains("<testcase classname=\"report\" name=\"POST /login\">"));
+        assert!(junit.contains("tests=\"2\" failures=\"1\""));
+    }
+
+    #[tokio::test]
+    async fn reports_are_written_to_their_paths() {
+        let directory =
+            std::env::temp_dir().join(format!("rocust_reports_{}", std::process::id()));
+        fs::create_dir_all(&directory).await.unwrap();
+        let path = |name: &str| Some(directory.join(name).to_string_lossy().to_string());
+        let mut test = create_test();
+        test.set_csv_report_path(path("results.csv"));
+        test.set_history_report_path(path("history.jsonl"));
+        test.set_junit_report_path(path("junit.xml"));
+        test.write_reports(&Logger::new(String::new(), false)).await;
+        let csv = fs::read_to_string(directory.join("results.csv")).await;
+        let junit = fs::read_to_string(directory.join("junit.xml")).await;
+        let history = fs::read_to_string(directory.join("history.jsonl")).await;
+        fs::remove_dir_all(&directory).await.unwrap();
+        assert!(csv.unwrap().starts_with(CSV_HEADER));
+        assert!(junit.unwrap().contains("<testsuite name=\"report\""));
+        assert_eq!(history.unwrap(), "");
+    }
+}
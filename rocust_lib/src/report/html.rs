@@ -0,0 +1,553 @@
+use super::escape_xml;
+use crate::{
+    history::Snapshot,
+    master::ResultsWebsocketMessage,
+    results::{format_optional_response_time, format_rate, format_response_time},
+    HasResults, Results, Runnable, Test,
+};
+use std::{collections::HashMap, error::Error};
+use tokio::fs;
+
+const CHART_WIDTH: f64 = 860.0;
+const CHART_HEIGHT: f64 = 260.0;
+const CHART_PADDING: f64 = 50.0;
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 24px; color: #222; background: #fafafa; }
+h1 { margin-bottom: 4px; }
+h2 { margin-top: 32px; border-bottom: 1px solid #ddd; padding-bottom: 4px; }
+table { border-collapse: collapse; margin-top: 8px; background: #fff; }
+th, td { border: 1px solid #ddd; padding: 4px 10px; text-align: right; font-size: 13px; }
+th { background: #f0f0f0; }
+td.name, th.name { text-align: left; }
+.failed { color: #c0392b; font-weight: bold; }
+.chart { background: #fff; border: 1px solid #ddd; margin-top: 8px; }
+.chart text { font-size: 11px; fill: #555; }
+.muted { color: #777; }
+"#;
+
+struct Line<'a> {
+    label: &'a str,
+    color: &'a str,
+    points: Vec<(f64, f64)>,
+}
+
+fn create_line_chart(title: &str, y_unit: &str, lines: &[Line]) -> String {
+    let max_x = lines
+        .iter()
+        .flat_map(|line| line.points.iter().map(|(x, _)| *x))
+        .fold(0.0, f64::max);
+    let max_y = lines
+        .iter()
+        .flat_map(|line| line.points.iter().map(|(_, y)| *y))
+        .fold(0.0, f64::max);
+    if max_x <= 0.0 {
+        return format!(
+            "<p class=\"muted\">{}: no data recorded</p>",
+            escape_xml(title)
+        );
+    }
+    let max_y = if max_y <= 0.0 { 1.0 } else { max_y * 1.1 };
+    let plot_width = CHART_WIDTH - 2.0 * CHART_PADDING;
+    let plot_height = CHART_HEIGHT - 2.0 * CHART_PADDING;
+    let scale_x = |x: f64| CHART_PADDING + x / max_x * plot_width;
+    let scale_y = |y: f64| CHART_HEIGHT - CHART_PADDING - y / max_y * plot_height;
+
+    let mut svg = format!(
+        "<svg class=\"chart\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" xmlns=\"http://www.w3.org/2000/svg\">\n<text x=\"{p}\" y=\"20\" style=\"font-size:14px;fill:#222\">{t}</text>\n",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT,
+        p = CHART_PADDING,
+        t = escape_xml(title)
+    );
+    // grid and axis labels
+    for i in 0..=4 {
+        let value = max_y / 4.0 * i as f64;
+        let y = scale_y(value);
+        svg.push_str(&format!(
+            "<line x1=\"{x1}\" y1=\"{y}\" x2=\"{x2}\" y2=\"{y}\" stroke=\"#eee\"/>\n<text x=\"{tx}\" y=\"{ty}\" text-anchor=\"end\">{v:.1}</text>\n",
+            x1 = CHART_PADDING,
+            x2 = CHART_WIDTH - CHART_PADDING,
+            y = y,
+            tx = CHART_PADDING - 6.0,
+            ty = y + 4.0,
+            v = value
+        ));
+        let elapsed = max_x / 4.0 * i as f64;
+        svg.push_str(&format!(
+            "<text x=\"{x}\" y=\"{y}\" text-anchor=\"middle\">{v:.0}s</text>\n",
+            x = scale_x(elapsed),
+            y = CHART_HEIGHT - CHART_PADDING + 16.0,
+            v = elapsed
+        ));
+    }
+    svg.push_str(&format!(
+        "<text x=\"{x}\" y=\"{y}\" text-anchor=\"end\">{u}</text>\n",
+        x = CHART_WIDTH - CHART_PADDING,
+        y = 20,
+        u = escape_xml(y_unit)
+    ));
+    for (index, line) in lines.iter().enumerate() {
+        let points: Vec<String> = line
+            .points
+            .iter()
+            .map(|(x, y)| format!("{:.1},{:.1}", scale_x(*x), scale_y(*y)))
+            .collect();
+        svg.push_str(&format!(
+            "<polyline fill=\"none\" stroke=\"{c}\" stroke-width=\"2\" points=\"{p}\"/>\n<rect x=\"{lx}\" y=\"{ly}\" width=\"10\" height=\"10\" fill=\"{c}\"/>\n<text x=\"{tx}\" y=\"{ty}\">{l}</text>\n",
+            c = line.color,
+            p = points.join(" "),
+            lx = CHART_PADDING + index as f64 * 120.0,
+            ly = CHART_HEIGHT - 18.0,
+            tx = CHART_PADDING + index as f64 * 120.0 + 14.0,
+            ty = CHART_HEIGHT - 9.0,
+            l = escape_xml(line.label)
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn create_points(snapshots: &[&Snapshot], value: impl Fn(&Snapshot) -> f64) -> Vec<(f64, f64)> {
+    snapshots
+        .iter()
+        .map(|snapshot| (snapshot.elapsed, value(snapshot)))
+        .collect()
+}
+
+fn create_charts(test: &Test) -> String {
+    let history = test.get_history().read();
+    let snapshots: Vec<&Snapshot> = history.get_aggregate().get_snapshots().iter().collect();
+    let requests_chart = create_line_chart(
+        "Requests per second",
+        "req/s",
+        &[
+            Line {
+                label: "Requests/s",
+                color: "#2980b9",
+                points: create_points(&snapshots, |s| s.requests_per_second),
+            },
+            Line {
+                label: "Failed/s",
+                color: "#c0392b",
+                points: create_points(&snapshots, |s| s.failed_requests_per_second),
+            },
+        ],
+    );
+    let response_times_chart = create_line_chart(
+        "Response times",
+        "ms",
+        &[
+            Line {
+                label: "Median",
+                color: "#27ae60",
+                points: create_points(&snapshots, |s| s.median_response_time as f64 / 1000.0),
+            },
+            Line {
+                label: "P95",
+                color: "#f39c12",
+                points: create_points(&snapshots, |s| {
+                    s.percentile_95_response_time as f64 / 1000.0
+                }),
+            },
+            Line {
+                label: "P99",
+                color: "#8e44ad",
+                points: create_points(&snapshots, |s| {
+                    s.percentile_99_response_time as f64 / 1000.0
+                }),
+            },
+        ],
+    );
+    let users_chart = create_line_chart(
+        "Active users",
+        "users",
+        &[Line {
+            label: "Users",
+            color: "#34495e",
+            points: create_points(&snapshots, |s| s.active_users as f64),
+        }],
+    );
+    format!("{}{}{}", requests_chart, response_times_chart, users_chart)
+}
+
+fn create_results_row(name: &str, mix: [String; 3], results: &Results) -> String {
+    let [weight, expected_mix, actual_mix] = mix;
+    let failed_class = if results.total_failed_requests > 0 || results.total_connection_errors > 0 {
+        " class=\"failed\""
+    } else {
+        ""
+    };
+    format!(
+        "<tr><td class=\"name\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td{}>{}</td><td{}>{}</td><td>{:.2}</td><td>{:.2}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+        escape_xml(name),
+        weight,
+        expected_mix,
+        actual_mix,
+        results.total_requests,
+        failed_class,
+        results.total_failed_requests,
+        failed_class,
+        results.total_connection_errors,
+        results.requests_per_second,
+        results.failed_requests_per_second,
+        format_response_time(results.average_response_time),
+        format_optional_response_time(results.min_response_time),
+        format_response_time(results.median_response_time),
+        format_response_time(results.percentile_90_response_time),
+        format_response_time(results.percentile_95_response_time),
+        format_response_time(results.percentile_99_response_time),
+        format_response_time(results.percentile_999_response_time),
+        format_optional_response_time(results.max_response_time),
+    )
+}
+
+const CORRECTED_RESULTS_HEADER: &str = "<table>\n<tr><th class=\"name\">Name</th><th>Median (ms)</th><th>Median Corr (ms)</th><th>P90 (ms)</th><th>P90 Corr (ms)</th><th>P95 (ms)</th><th>P95 Corr (ms)</th><th>P99 (ms)</th><th>P99 Corr (ms)</th><th>P99.9 (ms)</th><th>P99.9 Corr (ms)</th></tr>\n";
+
+const RESULTS_HEADER: &str = "<table>\n<tr><th class=\"name\">Name</th><th>Weight</th><th>Exp Mix %</th><th>Mix %</th><th>Requests</th><th>Failed</th><th>Conn Errors</th><th>Req/s</th><th>Failed Req/s</th><th>Avg (ms)</th><th>Min (ms)</th><th>Median (ms)</th><th>P90 (ms)</th><th>P95 (ms)</th><th>P99 (ms)</th><th>P99.9 (ms)</th><th>Max (ms)</th></tr>\n";
+
+fn create_results_table(test: &Test) -> String {
+    let mut table = String::from(RESULTS_HEADER);
+    for endpoint in test.get_endpoints().iter() {
+        let name = format!("{} {}", endpoint.get_method(), endpoint.get_url());
+        let mix = [
+            endpoint.get_weight().to_string(),
+            format_rate(test.get_expected_mix(endpoint)),
+            format_rate(test.get_actual_mix(endpoint)),
+        ];
+        table.push_str(&create_results_row(
+            &name,
+            mix,
+            &endpoint.get_results().read(),
+        ));
+    }
+    let mix = [
+        test.get_total_weight().to_string(),
+        String::new(),
+        String::new(),
+    ];
+    table.push_str(&create_results_row(
+        "Aggregated",
+        mix,
+        &test.get_results().read(),
+    ));
+    table.push_str("</table>\n");
+    table
+}
+
+fn create_corrected_results_row(name: &str, results: &Results) -> String {
+    format!(
+        "<tr><td class=\"name\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+        escape_xml(name),
+        format_response_time(results.median_response_time),
+        format_response_time(results.corrected_median_response_time),
+        format_response_time(results.percentile_90_response_time),
+        format_response_time(results.corrected_percentile_90_response_time),
+        format_response_time(results.percentile_95_response_time),
+        format_response_time(results.corrected_percentile_95_response_time),
+        format_response_time(results.percentile_99_response_time),
+        format_response_time(results.corrected_percentile_99_response_time),
+        format_response_time(results.percentile_999_response_time),
+        format_response_time(results.corrected_percentile_999_response_time),
+    )
+}
+
+// raw and corrected percentiles of the endpoints, empty if the correction is off
+fn create_corrected_results_table(test: &Test) -> String {
+    let Some(expected_interval) = test.get_expected_interval() else {
+        return String::new();
+    };
+    let mut table = format!(
+        "<h2>Corrected latency</h2>\n<p class=\"muted\">Corrected for coordinated omission, expected interval [{} ms]</p>\n{}",
+        expected_interval, CORRECTED_RESULTS_HEADER
+    );
+    for endpoint in test.get_endpoints().iter() {
+        let name = format!("{} {}", endpoint.get_method(), endpoint.get_url());
+        table.push_str(&create_corrected_results_row(
+            &name,
+            &endpoint.get_results().read(),
+        ));
+    }
+    table.push_str(&create_corrected_results_row(
+        "Aggregated",
+        &test.get_results().read(),
+    ));
+    table.push_str("</table>\n");
+    table
+}
+
+// scenario rows hold the transaction results, the rows below them the results of their steps
+fn create_scenarios_table(test: &Test) -> String {
+    if test.get_scenarios().is_empty() {
+        return String::new();
+    }
+    let mut table = String::from(RESULTS_HEADER);
+    for scenario in test.get_scenarios().iter() {
+        let mix = [
+            scenario.get_weight().to_string(),
+            format_rate(test.get_expected_scenario_mix(scenario)),
+            format_rate(test.get_actual_scenario_mix(scenario)),
+        ];
+        table.push_str(&create_results_row(
+            scenario.get_name(),
+            mix,
+            &scenario.get_results().read(),
+        ));
+        for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+            let name = format!(
+                "{} {} {}",
+                scenario.get_step_id(index),
+                endpoint.get_method(),
+                endpoint.get_url()
+            );
+            let mix = [String::new(), String::new(), String::new()];
+            table.push_str(&create_results_row(
+                &name,
+                mix,
+                &endpoint.get_results().read(),
+            ));
+        }
+    }
+    table.push_str("</table>\n");
+    format!("<h2>Scenarios</h2>\n{}", table)
+}
+
+// class rows hold the results of all users of the class, the rows above them the results of its endpoints and scenarios
+fn create_user_classes_table(test: &Test) -> String {
+    if test.get_user_classes().is_empty() {
+        return String::new();
+    }
+    let mut table = String::from(RESULTS_HEADER);
+    for user_class in test.get_user_classes().iter() {
+        for endpoint in user_class.get_endpoints().iter() {
+            let name = format!(
+                "{} {}",
+                endpoint.get_method(),
+                user_class.get_endpoint_id(endpoint)
+            );
+            let mix = [
+                endpoint.get_weight().to_string(),
+                format_rate(user_class.get_expected_mix(endpoint)),
+                format_rate(user_class.get_actual_mix(endpoint)),
+            ];
+            table.push_str(&create_results_row(
+                &name,
+                mix,
+                &endpoint.get_results().read(),
+            ));
+        }
+        for scenario in user_class.get_scenarios().iter() {
+            let mix = [scenario.get_weight().to_string(), String::new(), String::new()];
+            table.push_str(&create_results_row(
+                &user_class.get_scenario_id(scenario),
+                mix,
+                &scenario.get_results().read(),
+            ));
+            for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                let name = format!(
+                    "{} {} {}",
+                    user_class.get_step_id(scenario, index),
+                    endpoint.get_method(),
+                    endpoint.get_url()
+                );
+                let mix = [String::new(), String::new(), String::new()];
+                table.push_str(&create_results_row(
+                    &name,
+                    mix,
+                    &endpoint.get_results().read(),
+                ));
+            }
+        }
+        let mix = [
+            user_class.get_weight().to_string(),
+            format_rate(test.get_expected_user_class_mix(user_class)),
+            format_rate(test.get_actual_user_class_mix(user_class)),
+        ];
+        table.push_str(&create_results_row(
+            user_class.get_name(),
+            mix,
+            &user_class.get_results().read(),
+        ));
+    }
+    table.push_str("</table>\n");
+    format!("<h2>User classes</h2>\n{}", table)
+}
+
+fn create_errors_table(test: &Test) -> String {
+    let mut rows = String::new();
+    for (kind, id, results) in test.get_all_results() {
+        let results = results.read();
+        if results.total_failed_requests == 0 && results.total_connection_errors == 0 {
+            continue;
+        }
+        let mut failed_checks: Vec<(&String, &u64)> = results.failed_checks.iter().collect();
+        failed_checks.sort();
+        let failed_checks: Vec<String> = failed_checks
+            .iter()
+            .map(|(name, count)| format!("{} ({})", escape_xml(name), count))
+            .collect();
+        rows.push_str(&format!(
+            "<tr><td class=\"name\">{}</td><td class=\"name\">{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"name\">{}</td><td class=\"name\">{}</td></tr>\n",
+            kind,
+            escape_xml(&id),
+            results.total_failed_requests,
+            results.total_connection_errors,
+            format_rate(
+                results.total_failed_requests as f64 / results.total_requests.max(1) as f64
+            ),
+            escape_xml(&results.errors.to_string()),
+            failed_checks.join(", "),
+        ));
+    }
+    if rows.is_empty() {
+        return String::from("<p class=\"muted\">No errors recorded</p>\n");
+    }
+    let mut samples = String::new();
+    for sample in test.get_results().read().errors.samples.iter() {
+        samples.push_str(&format!(
+            "<tr><td class=\"name\">{}</td><td>{}</td><td class=\"name\">{}</td></tr>\n",
+            sample.kind,
+            sample.count,
+            escape_xml(&sample.message)
+        ));
+    }
+    let mut tables = format!(
+        "<table>\n<tr><th class=\"name\">Type</th><th class=\"name\">Name</th><th>Failed Requests</th><th>Connection Errors</th><th>Failed %</th><th class=\"name\">Status Codes and Errors</th><th class=\"name\">Failed Checks</th></tr>\n{}</table>\n",
+        rows
+    );
+    if !samples.is_empty() {
+        tables.push_str(&format!(
+            "<table>\n<tr><th class=\"name\">Error</th><th>Count</th><th class=\"name\">Sample Message</th></tr>\n{}</table>\n",
+            samples
+        ));
+    }
+    tables
+}
+
+fn create_thresholds_table(test: &Test) -> String {
+    if test.get_thresholds().is_empty() {
+        return String::from("<p class=\"muted\">No thresholds defined</p>\n");
+    }
+    let mut table =
+        String::from("<table>\n<tr><th class=\"name\">Threshold</th><th>Result</th></tr>\n");
+    for threshold in test.get_thresholds().iter() {
+        let result = match test.evaluate_threshold(threshold) {
+            Some(breach) => format!(
+                "<td class=\"failed\">FAILED (actual {:.3})</td>",
+                breach.actual
+            ),
+            None => String::from("<td>PASSED</td>"),
+        };
+        table.push_str(&format!(
+            "<tr><td class=\"name\">{}</td>{}</tr>\n",
+            escape_xml(&threshold.to_string()),
+            result
+        ));
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+fn create_workers_table(workers_results: &HashMap<String, ResultsWebsocketMessage>) -> String {
+    let mut table = String::from(
+        "<table>\n<tr><th class=\"name\">Worker</th><th>Requests</th><th>Failed</th><th>Conn Errors</th><th>Active Users</th></tr>\n",
+    );
+    let mut worker_ids: Vec<&String> = workers_results.keys().collect();
+    worker_ids.sort();
+    for worker_id in worker_ids {
+        let results_websocket_message = &workers_results[worker_id];
+        let agg_sent_results = results_websocket_message.get_agg_sent_results();
+        table.push_str(&format!(
+            "<tr><td class=\"name\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_xml(worker_id),
+            agg_sent_results.total_requests,
+            agg_sent_results.total_failed_requests,
+            agg_sent_results.total_connection_errors,
+            results_websocket_message.get_active_users(),
+        ));
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+/// A self contained HTML report. Everything is inlined, so it can be opened without a server.
+pub fn create_html(
+    test: &Test,
+    workers_results: Option<&HashMap<String, ResultsWebsocketMessage>>,
+) -> String {
+    let elapsed = test
+        .get_elapsed_time()
+        .map(|elapsed| format!("{:.1}s", elapsed.as_secs_f64()))
+        .unwrap_or_else(|| String::from("-"));
+    let workers = match workers_results {
+        Some(workers_results) => format!(
+            "<h2>Workers</h2>\n{}",
+            create_workers_table(workers_results)
+        ),
+        None => String::new(),
+    };
+    let dropped_iterations = match &**test.get_arrival_rate() {
+        Some(_) => format!(
+            " | Dropped iterations [{}]",
+            test.get_results().read().dropped_iterations
+        ),
+        None => String::new(),
+    };
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Rocust report - {id}</title>\n<style>{style}</style>\n</head>\n<body>\n<h1>Rocust report - {id}</h1>\n<p class=\"muted\">Status [{status}] | Users [{users}] | Elapsed [{elapsed}]{dropped_iterations} | Host [{host}]</p>\n<h2>Summary</h2>\n{results}{corrected}{scenarios}{user_classes}<h2>Thresholds</h2>\n{thresholds}<h2>Charts</h2>\n{charts}<h2>Errors</h2>\n{errors}{workers}</body>\n</html>\n",
+        id = escape_xml(test.get_id()),
+        style = STYLE,
+        status = escape_xml(&test.get_status().to_string()),
+        users = test.get_user_count(),
+        elapsed = elapsed,
+        dropped_iterations = dropped_iterations,
+        host = escape_xml(test.get_host()),
+        results = create_results_table(test),
+        corrected = create_corrected_results_table(test),
+        scenarios = create_scenarios_table(test),
+        user_classes = create_user_classes_table(test),
+        thresholds = create_thresholds_table(test),
+        charts = create_charts(test),
+        errors = create_errors_table(test),
+        workers = workers,
+    )
+}
+
+pub async fn write_html(
+    test: &Test,
+    workers_results: Option<&HashMap<String, ResultsWebsocketMessage>>,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    fs::write(path, create_html(test, workers_results)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scenario::Step, EndPoint, Scenario};
+
+    #[test]
+    fn errors_of_scenario_steps_are_listed() {
+        let mut test = Test::new(
+            String::from("report"),
+            1,
+            None,
+            (0, 0),
+            String::from("http://127.0.0.1"),
+            vec![],
+            None,
+            String::new(),
+            false,
+            false,
+        );
+        let step = EndPoint::new_get(String::from("/cart"), None, None);
+        step.get_results().write().add_failed(503);
+        test.set_scenarios(vec![Scenario::new(
+            String::from("checkout"),
+            vec![Step::Request(Box::new(step))],
+        )]);
+        let errors = create_errors_table(&test);
+        assert!(errors.contains("<td class=\"name\">Step</td><td class=\"name\">checkout/1</td>"));
+    }
+}
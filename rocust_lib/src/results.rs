@@ -1,30 +1,74 @@
+use crate::{errors::ErrorKind, ErrorBreakdown, Histogram};
 use serde::{Deserialize, Serialize};
-use std::{fmt, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    time::Duration,
+};
 
+// all response times are stored in microseconds
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SentResults {
-    pub total_requests: u32,
-    pub total_failed_requests: u32,
-    pub total_connection_errors: u32,
-    pub total_response_time: u32,
-    //pub average_response_time: u32,
-    pub min_response_time: u32,
-    pub median_response_time: u32,
-    pub max_response_time: u32,
+    pub total_requests: u64,
+    pub total_failed_requests: u64,
+    pub total_connection_errors: u64,
+    pub total_response_time: u64,
+    //pub average_response_time: u64,
+    pub min_response_time: Option<u64>,
+    pub max_response_time: Option<u64>,
+    pub histogram: Histogram,
+    pub corrected_histogram: Histogram,
+    pub failed_checks: HashMap<String, u64>,
+    pub errors: ErrorBreakdown,
+    pub dropped_iterations: u64,
 }
 
+// all response times are stored in microseconds
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Results {
-    pub total_requests: u32,
-    pub total_failed_requests: u32,
-    pub total_connection_errors: u32,
-    pub total_response_time: u32,
-    pub average_response_time: u32,
-    pub min_response_time: u32,
-    pub median_response_time: u32,
-    pub max_response_time: u32,
+    pub total_requests: u64,
+    pub total_failed_requests: u64,
+    pub total_connection_errors: u64,
+    pub total_response_time: u64,
+    pub average_response_time: u64,
+    pub min_response_time: Option<u64>,
+    pub median_response_time: u64,
+    pub percentile_90_response_time: u64,
+    pub percentile_95_response_time: u64,
+    pub percentile_99_response_time: u64,
+    pub percentile_999_response_time: u64,
+    pub max_response_time: Option<u64>,
+    pub corrected_median_response_time: u64, //CORRECTED FOR COORDINATED OMISSION
+    pub corrected_percentile_90_response_time: u64,
+    pub corrected_percentile_95_response_time: u64,
+    pub corrected_percentile_99_response_time: u64,
+    pub corrected_percentile_999_response_time: u64,
     pub requests_per_second: f64,
     pub failed_requests_per_second: f64,
+    pub current_requests_per_second: f64, //OVER THE TRAILING WINDOW
+    pub current_failed_requests_per_second: f64, //OVER THE TRAILING WINDOW
+    pub current_failure_rate: f64, //OVER THE TRAILING WINDOW
+    pub histogram: Histogram,
+    pub corrected_histogram: Histogram, //EMPTY IF THE COORDINATED OMISSION CORRECTION IS OFF
+    pub failed_checks: HashMap<String, u64>, //CHECK NAME -> COUNT
+    pub errors: ErrorBreakdown,
+    pub dropped_iterations: u64, //ITERATIONS NOT STARTED AT THE ARRIVAL RATE, ALL USERS WERE BUSY
+    #[serde(skip)]
+    window_samples: VecDeque<WindowSample>,
+}
+
+// totals at a point in time, used to calculate the current requests per second
+#[derive(Clone, Debug)]
+struct WindowSample {
+    elapsed: f64,
+    total_requests: u64,
+    total_failed_requests: u64,
+}
+
+impl Default for Results {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Results {
@@ -35,16 +79,37 @@ impl Results {
             total_connection_errors: 0,
             total_response_time: 0,
             average_response_time: 0,
-            min_response_time: 0,
+            min_response_time: None,
             median_response_time: 0,
-            max_response_time: 0,
+            percentile_90_response_time: 0,
+            percentile_95_response_time: 0,
+            percentile_99_response_time: 0,
+            percentile_999_response_time: 0,
+            max_response_time: None,
+            corrected_median_response_time: 0,
+            corrected_percentile_90_response_time: 0,
+            corrected_percentile_95_response_time: 0,
+            corrected_percentile_99_response_time: 0,
+            corrected_percentile_999_response_time: 0,
             requests_per_second: 0.0,
             failed_requests_per_second: 0.0,
+            current_requests_per_second: 0.0,
+            current_failed_requests_per_second: 0.0,
+            current_failure_rate: 0.0,
+            histogram: Histogram::new(),
+            corrected_histogram: Histogram::new(),
+            failed_checks: HashMap::new(),
+            errors: ErrorBreakdown::new(),
+            dropped_iterations: 0,
+            window_samples: VecDeque::new(),
         }
     }
 
+    // the window samples describe the past and are kept, so the current requests per second survive a reset and recombine
     pub fn reset(&mut self) {
+        let window_samples = std::mem::take(&mut self.window_samples);
         *self = Results::new();
+        self.window_samples = window_samples;
     }
 
     pub fn create_sent_results(&self) -> SentResults {
@@ -55,55 +120,121 @@ impl Results {
             total_response_time: self.total_response_time,
             //average_response_time: self.average_response_time,
             min_response_time: self.min_response_time,
-            median_response_time: self.median_response_time,
             max_response_time: self.max_response_time,
+            histogram: self.histogram.clone(),
+            corrected_histogram: self.corrected_histogram.clone(),
+            failed_checks: self.failed_checks.clone(),
+            errors: self.errors.clone(),
+            dropped_iterations: self.dropped_iterations,
         }
     }
 
     pub fn combine_sent_results(&mut self, sent_results: &SentResults) {
-        self.total_requests += sent_results.total_requests;
-        self.total_failed_requests += sent_results.total_failed_requests;
-        self.total_connection_errors += sent_results.total_connection_errors;
-        self.total_response_time += sent_results.total_response_time;
+        self.total_requests = self
+            .total_requests
+            .saturating_add(sent_results.total_requests);
+        self.total_failed_requests = self
+            .total_failed_requests
+            .saturating_add(sent_results.total_failed_requests);
+        self.total_connection_errors = self
+            .total_connection_errors
+            .saturating_add(sent_results.total_connection_errors);
+        self.total_response_time = self
+            .total_response_time
+            .saturating_add(sent_results.total_response_time);
 
-        if self.total_requests > 0 {
-            self.average_response_time = self.total_response_time / self.total_requests;
+        if let Some(average_response_time) =
+            self.total_response_time.checked_div(self.total_requests)
+        {
+            self.average_response_time = average_response_time;
+        }
+        if let Some(min_response_time) = sent_results.min_response_time {
+            self.set_min_response_time(min_response_time);
         }
-        if self.min_response_time == 0 || sent_results.min_response_time < self.min_response_time {
-            self.min_response_time = sent_results.min_response_time;
+        if let Some(max_response_time) = sent_results.max_response_time {
+            self.set_max_response_time(max_response_time);
         }
-        if sent_results.max_response_time > self.max_response_time {
-            self.max_response_time = sent_results.max_response_time;
+        self.histogram.merge(&sent_results.histogram);
+        self.corrected_histogram
+            .merge(&sent_results.corrected_histogram);
+        for (name, count) in sent_results.failed_checks.iter() {
+            let failed_checks = self.failed_checks.entry(name.clone()).or_insert(0);
+            *failed_checks = failed_checks.saturating_add(*count);
         }
-        //self.median_response_time = 
+        self.errors.merge(&sent_results.errors);
+        self.dropped_iterations = self
+            .dropped_iterations
+            .saturating_add(sent_results.dropped_iterations);
     }
 
-    pub fn add_response_time(&mut self, response_time: u32) {
-        self.total_response_time += response_time;
-        self.total_requests += 1;
-        self.average_response_time = self.total_response_time / self.total_requests;
-        if self.min_response_time == 0 || response_time < self.min_response_time {
-            self.min_response_time = response_time;
+    pub fn add_dropped_iteration(&mut self) {
+        self.dropped_iterations = self.dropped_iterations.saturating_add(1);
+    }
+
+    fn set_min_response_time(&mut self, response_time: u64) {
+        match self.min_response_time {
+            Some(min_response_time) if min_response_time <= response_time => {}
+            _ => self.min_response_time = Some(response_time),
         }
-        if response_time > self.max_response_time {
-            self.max_response_time = response_time;
+    }
+
+    fn set_max_response_time(&mut self, response_time: u64) {
+        match self.max_response_time {
+            Some(max_response_time) if max_response_time >= response_time => {}
+            _ => self.max_response_time = Some(response_time),
         }
     }
 
-    pub fn add_failed(&mut self) {
-        self.total_requests += 1;
-        self.total_failed_requests += 1;
+    pub fn add_response_time(&mut self, response_time: u64) {
+        self.total_response_time = self.total_response_time.saturating_add(response_time);
+        self.total_requests = self.total_requests.saturating_add(1);
+        self.average_response_time = self.total_response_time / self.total_requests;
+        self.set_min_response_time(response_time);
+        self.set_max_response_time(response_time);
+        self.histogram.record(response_time);
+    }
+
+    fn count_failed(&mut self) {
+        self.total_requests = self.total_requests.saturating_add(1);
+        self.total_failed_requests = self.total_failed_requests.saturating_add(1);
     }
 
-    pub fn add_connection_error(&mut self) {
-        self.total_connection_errors += 1;
+    // the corrected values of a response are computed once and merged into every results it counts for
+    pub fn add_corrected_histogram(&mut self, corrected_histogram: &Histogram) {
+        self.corrected_histogram.merge(corrected_histogram);
     }
 
-    pub fn get_total_requests(&self) -> u32 {
+    pub fn add_failed(&mut self, status_code: u16) {
+        self.count_failed();
+        self.errors.add_status_code(status_code);
+    }
+
+    // a failed check is a failed request
+    pub fn add_failed_check(&mut self, name: &str, reason: &str) {
+        self.count_failed();
+        let failed_checks = self.failed_checks.entry(name.to_string()).or_insert(0);
+        *failed_checks = failed_checks.saturating_add(1);
+        self.errors
+            .add_error(ErrorKind::Check, &format!("{}: {}", name, reason));
+    }
+
+    // a failed step is a failed scenario run
+    pub fn add_failed_step(&mut self, step: &str, reason: &str) {
+        self.count_failed();
+        self.errors
+            .add_error(ErrorKind::Step, &format!("{}: {}", step, reason));
+    }
+
+    pub fn add_connection_error(&mut self, kind: ErrorKind, message: &str) {
+        self.total_connection_errors = self.total_connection_errors.saturating_add(1);
+        self.errors.add_error(kind, message);
+    }
+
+    pub fn get_total_requests(&self) -> u64 {
         self.total_requests
     }
 
-    pub fn get_total_failed_requests(&self) -> u32 {
+    pub fn get_total_failed_requests(&self) -> u64 {
         self.total_failed_requests
     }
 
@@ -126,14 +257,189 @@ impl Results {
         let failed_requests_per_second = total_failed_requests as f64 / elapsed.as_secs_f64();
         self.set_failed_requests_per_second(failed_requests_per_second);
     }
+
+    pub fn calculate_current_requests_per_second(&mut self, elapsed: &Duration, window: &Duration) {
+        let now = elapsed.as_secs_f64();
+        let window_start = now - window.as_secs_f64();
+        if self.window_samples.is_empty() {
+            // the test started with no requests
+            self.window_samples.push_back(WindowSample {
+                elapsed: 0.0,
+                total_requests: 0,
+                total_failed_requests: 0,
+            });
+        }
+        self.window_samples.push_back(WindowSample {
+            elapsed: now,
+            total_requests: self.total_requests,
+            total_failed_requests: self.total_failed_requests,
+        });
+        // keep the newest sample that is older than the window start as the baseline
+        while self.window_samples.len() > 2 && self.window_samples[1].elapsed <= window_start {
+            self.window_samples.pop_front();
+        }
+        let baseline = &self.window_samples[0];
+        let interval = now - baseline.elapsed;
+        let requests = self.total_requests.saturating_sub(baseline.total_requests);
+        let failed_requests = self
+            .total_failed_requests
+            .saturating_sub(baseline.total_failed_requests);
+        if interval > 0.0 {
+            self.current_requests_per_second = requests as f64 / interval;
+            self.current_failed_requests_per_second = failed_requests as f64 / interval;
+        }
+        self.current_failure_rate = if requests > 0 {
+            failed_requests as f64 / requests as f64
+        } else {
+            0.0
+        };
+    }
+
+    pub fn get_percentile_response_time(&self, percentile: f64) -> u64 {
+        let response_time = self.histogram.value_at_percentile(percentile);
+        // the histogram only knows the bucket, the recorded extremes are exact
+        match (self.min_response_time, self.max_response_time) {
+            (Some(min), Some(max)) => response_time.clamp(min, max),
+            _ => response_time,
+        }
+    }
+
+    // back-filled values are below the recorded maximum, but may be below the recorded minimum
+    pub fn get_corrected_percentile_response_time(&self, percentile: f64) -> u64 {
+        let response_time = self.corrected_histogram.value_at_percentile(percentile);
+        match self.max_response_time {
+            Some(max) => response_time.min(max),
+            None => response_time,
+        }
+    }
+
+    // walking the histogram is too expensive to do on every response, so percentiles are refreshed periodically
+    pub fn calculate_percentiles(&mut self) {
+        self.median_response_time = self.get_percentile_response_time(50.0);
+        self.percentile_90_response_time = self.get_percentile_response_time(90.0);
+        self.percentile_95_response_time = self.get_percentile_response_time(95.0);
+        self.percentile_99_response_time = self.get_percentile_response_time(99.0);
+        self.percentile_999_response_time = self.get_percentile_response_time(99.9);
+        if !self.corrected_histogram.is_empty() {
+            self.corrected_median_response_time = self.get_corrected_percentile_response_time(50.0);
+            self.corrected_percentile_90_response_time =
+                self.get_corrected_percentile_response_time(90.0);
+            self.corrected_percentile_95_response_time =
+                self.get_corrected_percentile_response_time(95.0);
+            self.corrected_percentile_99_response_time =
+                self.get_corrected_percentile_response_time(99.0);
+            self.corrected_percentile_999_response_time =
+                self.get_corrected_percentile_response_time(99.9);
+        }
+    }
+}
+
+/// Formats a response time given in microseconds as milliseconds.
+pub fn format_response_time(response_time: u64) -> String {
+    format!("{:.3}", response_time as f64 / 1000.0)
+}
+
+/// Formats a rate between 0 and 1 as a percentage.
+pub fn format_rate(rate: f64) -> String {
+    format!("{:.2}", rate * 100.0)
+}
+
+/// Same as [`format_response_time`], with `-` for a response time that was never recorded.
+pub fn format_optional_response_time(response_time: Option<u64>) -> String {
+    match response_time {
+        Some(response_time) => format_response_time(response_time),
+        None => String::from("-"),
+    }
 }
 
 impl fmt::Display for Results {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Total Requests [{}] | Requests per Second [{}] | Total Response Time [{}] | Average Response Time [{}]",
-            self.total_requests, self.requests_per_second, self.total_response_time, self.average_response_time
+            "Total Requests [{}] | Requests per Second [{}] | Total Response Time [{} ms] | Average Response Time [{} ms] | Median Response Time [{} ms] | 95th Percentile Response Time [{} ms] | 99th Percentile Response Time [{} ms]",
+            self.total_requests,
+            self.requests_per_second,
+            format_response_time(self.total_response_time),
+            format_response_time(self.average_response_time),
+            format_response_time(self.median_response_time),
+            format_response_time(self.percentile_95_response_time),
+            format_response_time(self.percentile_99_response_time)
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_and_max_are_unset_until_a_response_is_recorded() {
+        let mut results = Results::new();
+        assert_eq!(results.min_response_time, None);
+        assert_eq!(results.max_response_time, None);
+        assert_eq!(
+            format_optional_response_time(results.min_response_time),
+            "-"
+        );
+        results.add_failed(500);
+        assert_eq!(results.min_response_time, None);
+        results.add_response_time(1_500);
+        results.add_response_time(250);
+        results.add_response_time(900);
+        assert_eq!(results.min_response_time, Some(250));
+        assert_eq!(results.max_response_time, Some(1_500));
+        assert_eq!(
+            format_optional_response_time(results.min_response_time),
+            "0.250"
+        );
+    }
+
+    #[test]
+    fn combining_keeps_the_extremes_of_both_sides() {
+        let mut results = Results::new();
+        results.combine_sent_results(&Results::new().create_sent_results());
+        assert_eq!(results.min_response_time, None);
+        assert_eq!(results.max_response_time, None);
+        let mut sent_results = Results::new();
+        sent_results.add_response_time(40);
+        sent_results.add_response_time(70);
+        results.add_response_time(50);
+        results.add_response_time(90);
+        results.combine_sent_results(&sent_results.create_sent_results());
+        assert_eq!(results.min_response_time, Some(40));
+        assert_eq!(results.max_response_time, Some(90));
+        assert_eq!(results.total_requests, 4);
+        assert_eq!(results.average_response_time, 62);
+        // an empty worker does not reset the extremes
+        results.combine_sent_results(&Results::new().create_sent_results());
+        assert_eq!(results.min_response_time, Some(40));
+        assert_eq!(results.max_response_time, Some(90));
+    }
+
+    #[test]
+    fn current_requests_per_second_only_count_the_window() {
+        let window = Duration::from_secs(10);
+        let mut results = Results::new();
+        for _ in 0..100 {
+            results.add_response_time(10);
+        }
+        results.calculate_current_requests_per_second(&Duration::from_secs(10), &window);
+        assert_eq!(results.current_requests_per_second, 10.0);
+        for _ in 0..20 {
+            results.add_response_time(10);
+        }
+        for _ in 0..20 {
+            results.add_failed(500);
+        }
+        results.calculate_current_requests_per_second(&Duration::from_secs(20), &window);
+        assert_eq!(results.current_requests_per_second, 4.0);
+        assert_eq!(results.current_failed_requests_per_second, 2.0);
+        assert_eq!(results.current_failure_rate, 0.5);
+        results.calculate_requests_per_second(&Duration::from_secs(20));
+        assert_eq!(results.requests_per_second, 7.0);
+        // no requests in the last window
+        results.calculate_current_requests_per_second(&Duration::from_secs(30), &window);
+        assert_eq!(results.current_requests_per_second, 0.0);
+        assert_eq!(results.current_failure_rate, 0.0);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{errors::ErrorKind, EndPoint, HasResults, Results};
+use parking_lot::RwLock;
+use rand::Rng;
+use serde::{
+    de::{MapAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+
+pub const DEFAULT_SCENARIO_WEIGHT: u32 = 1;
+
+// conditions are evaluated against the variables of the user running the scenario
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Condition {
+    Exists(String),
+    Equals { name: String, value: String },
+    NotEquals { name: String, value: String },
+    Probability(f64), //BETWEEN 0 AND 1
+}
+
+impl Condition {
+    pub fn evaluate(&self, variables: &HashMap<String, String>) -> bool {
+        match self {
+            Condition::Exists(name) => variables.contains_key(name),
+            Condition::Equals { name, value } => variables.get(name) == Some(value),
+            Condition::NotEquals { name, value } => variables.get(name) != Some(value),
+            Condition::Probability(probability) => {
+                rand::thread_rng().gen_bool(probability.clamp(0.0, 1.0))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Step {
+    Request(Box<EndPoint>), //THE RESULTS OF THE ENDPOINT ARE THE RESULTS OF THE STEP
+    Loop {
+        times: u32,
+        steps: Vec<Step>,
+    },
+    Branch {
+        condition: Condition,
+        steps: Vec<Step>,
+        else_steps: Vec<Step>,
+    },
+    Set {
+        name: String,
+        value: String,
+    },
+}
+
+impl Step {
+    fn collect_requests<'a>(&'a self, requests: &mut Vec<&'a EndPoint>) {
+        match self {
+            Step::Request(endpoint) => requests.push(endpoint),
+            Step::Loop { steps, .. } => {
+                for step in steps.iter() {
+                    step.collect_requests(requests);
+                }
+            }
+            Step::Branch {
+                steps, else_steps, ..
+            } => {
+                for step in steps.iter().chain(else_steps.iter()) {
+                    step.collect_requests(requests);
+                }
+            }
+            Step::Set { .. } => {}
+        }
+    }
+}
+
+/// An ordered flow of steps run by a user from start to end, like login, browse, add to cart and check out.
+/// The results of a scenario are the timings of whole runs (transactions), the results of its request steps are kept in their endpoints.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<Step>,
+    pub weight: u32,                   //RELATIVE TO THE OTHER SCENARIOS OF THE TEST
+    pub results: Arc<RwLock<Results>>, //TRANSACTION RESULTS
+}
+
+impl Scenario {
+    pub fn new(name: String, steps: Vec<Step>) -> Scenario {
+        Scenario {
+            name,
+            steps,
+            weight: DEFAULT_SCENARIO_WEIGHT,
+            results: Arc::new(RwLock::new(Results::new())),
+        }
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_steps(&self) -> &Vec<Step> {
+        &self.steps
+    }
+
+    pub fn get_weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn set_weight(&mut self, weight: u32) {
+        self.weight = weight;
+    }
+
+    pub fn get_results(&self) -> &Arc<RwLock<Results>> {
+        &self.results
+    }
+
+    // the endpoints of all request steps, nested steps included, in the order they are defined
+    pub fn get_requests(&self) -> Vec<&EndPoint> {
+        let mut requests = Vec::new();
+        for step in self.steps.iter() {
+            step.collect_requests(&mut requests);
+        }
+        requests
+    }
+
+    // identifies a request step across master and workers, index as in get_requests
+    pub fn get_step_id(&self, index: usize) -> String {
+        format!("{}/{}", self.name, index + 1)
+    }
+
+    // a failed step fails the whole run
+    pub fn add_failed_step(&self, step: &str, reason: &str) {
+        self.results.write().add_failed_step(step, reason);
+    }
+}
+
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Scenario [{}] | Steps [{}] | Results [{}]",
+            self.name,
+            self.steps.len(),
+            self.results.read()
+        )
+    }
+}
+
+impl HasResults for Scenario {
+    fn add_response_time(&self, response_time: u64) {
+        self.results.write().add_response_time(response_time);
+    }
+
+    fn add_failed(&self, status_code: u16) {
+        self.results.write().add_failed(status_code);
+    }
+
+    fn add_failed_check(&self, name: &str, reason: &str) {
+        self.results.write().add_failed_check(name, reason);
+    }
+
+    fn add_connection_error(&self, kind: ErrorKind, message: &str) {
+        self.results.write().add_connection_error(kind, message);
+    }
+
+    fn set_requests_per_second(&self, requests_per_second: f64) {
+        self.results
+            .write()
+            .set_requests_per_second(requests_per_second);
+    }
+
+    fn calculate_requests_per_second(&self, elapsed: &Duration) {
+        self.results.write().calculate_requests_per_second(elapsed);
+        for endpoint in self.get_requests() {
+            endpoint.calculate_requests_per_second(elapsed);
+        }
+    }
+
+    fn calculate_failed_requests_per_second(&self, elapsed: &Duration) {
+        self.results
+            .write()
+            .calculate_failed_requests_per_second(elapsed);
+        for endpoint in self.get_requests() {
+            endpoint.calculate_failed_requests_per_second(elapsed);
+        }
+    }
+
+    fn calculate_current_requests_per_second(&self, elapsed: &Duration, window: &Duration) {
+        self.results
+            .write()
+            .calculate_current_requests_per_second(elapsed, window);
+        for endpoint in self.get_requests() {
+            endpoint.calculate_current_requests_per_second(elapsed, window);
+        }
+    }
+
+    fn calculate_percentiles(&self) {
+        self.results.write().calculate_percentiles();
+        for endpoint in self.get_requests() {
+            endpoint.calculate_percentiles();
+        }
+    }
+
+    fn get_results(&self) -> Arc<RwLock<Results>> {
+        self.results.clone()
+    }
+
+    fn clone_results(&self) -> Results {
+        self.results.read().clone()
+    }
+}
+
+impl Serialize for Scenario {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Scenario", 4)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("steps", &self.steps)?;
+        state.serialize_field("weight", &self.weight)?;
+        state.serialize_field("results", &*self.results.read())?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Scenario {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ScenarioVisitor;
+
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "snake_case")]
+        enum Field {
+            Name,
+            Steps,
+            Weight,
+            Results,
+        }
+        impl<'de> Visitor<'de> for ScenarioVisitor {
+            type Value = Scenario;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("struct Scenario")
+            }
+
+            fn visit_map<V>(self, mut map: V) -> Result<Scenario, V::Error>
+            where
+                V: MapAccess<'de>,
+            {
+                let mut name: Option<String> = None;
+                let mut steps: Option<Vec<Step>> = None;
+                let mut weight: Option<u32> = None;
+                let mut results: Option<Results> = None;
+
+                while let Some(key) = map.next_key()? {
+                    match key {
+                        Field::Name => {
+                            if name.is_some() {
+                                return Err(serde::de::Error::duplicate_field("name"));
+                            }
+                            name = Some(map.next_value()?);
+                        }
+                        Field::Steps => {
+                            if steps.is_some() {
+                                return Err(serde::de::Error::duplicate_field("steps"));
+                            }
+                            steps = Some(map.next_value()?);
+                        }
+                        Field::Weight => {
+                            if weight.is_some() {
+                                return Err(serde::de::Error::duplicate_field("weight"));
+                            }
+                            weight = Some(map.next_value()?);
+                        }
+                        Field::Results => {
+                            if results.is_some() {
+                                return Err(serde::de::Error::duplicate_field("results"));
+                            }
+                            results = Some(map.next_value()?);
+                        }
+                    }
+                }
+                let name = name.ok_or_else(|| serde::de::Error::missing_field("name"))?;
+                let steps = steps.ok_or_else(|| serde::de::Error::missing_field("steps"))?;
+                let weight = weight.unwrap_or(DEFAULT_SCENARIO_WEIGHT);
+                let results = results.unwrap_or_default();
+
+                Ok(Scenario {
+                    name,
+                    steps,
+                    weight,
+                    results: Arc::new(RwLock::new(results)),
+                })
+            }
+        }
+        const FIELDS: &[&str] = &["name", "steps", "weight", "results"];
+        deserializer.deserialize_struct("Scenario", FIELDS, ScenarioVisitor)
+    }
+}
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, time::Duration};
+
+/// The users move in a straight line from the target of the previous stage to the target of this stage.
+/// A stage with a duration of 0 jumps to its target, a stage with the target of the previous stage holds it.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Stage {
+    pub duration: u64, //SECONDS
+    pub target: u32,   //USERS AT THE END OF THE STAGE
+}
+
+impl Stage {
+    pub fn new(duration: u64, target: u32) -> Stage {
+        Stage { duration, target }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Point {
+    pub at: u64, //SECONDS SINCE THE START OF THE TEST
+    pub users: u32,
+}
+
+impl Point {
+    pub fn new(at: u64, users: u32) -> Point {
+        Point { at, users }
+    }
+}
+
+/// The number of users over the time of a test. The test starts with 0 users and finishes when the shape ends.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum LoadShape {
+    Stages(Vec<Stage>),
+    Step {
+        start: u32,         //USERS OF THE FIRST STEP
+        step: u32,          //USERS ADDED BY EVERY FURTHER STEP
+        step_duration: u64, //SECONDS
+        steps: u32,
+    },
+    Spike {
+        base: u32,
+        peak: u32,
+        before: u64,         //SECONDS AT THE BASE BEFORE THE SPIKE
+        spike_duration: u64, //SECONDS AT THE PEAK
+        after: u64,          //SECONDS AT THE BASE AFTER THE SPIKE
+    },
+    Custom(Vec<Point>), //A CURVE THROUGH THE POINTS, STARTING AT 0 USERS
+}
+
+impl LoadShape {
+    // every shape is followed as a list of stages
+    pub fn to_stages(&self) -> Vec<Stage> {
+        match self {
+            LoadShape::Stages(stages) => stages.clone(),
+            LoadShape::Step {
+                start,
+                step,
+                step_duration,
+                steps,
+            } => (0..*steps)
+                .flat_map(|index| {
+                    let target = step.saturating_mul(index).saturating_add(*start);
+                    [Stage::new(0, target), Stage::new(*step_duration, target)]
+                })
+                .collect(),
+            LoadShape::Spike {
+                base,
+                peak,
+                before,
+                spike_duration,
+                after,
+            } => vec![
+                Stage::new(0, *base),
+                Stage::new(*before, *base),
+                Stage::new(0, *peak),
+                Stage::new(*spike_duration, *peak),
+                Stage::new(0, *base),
+                Stage::new(*after, *base),
+            ],
+            LoadShape::Custom(points) => {
+                let mut points = points.clone();
+                points.sort_by_key(|point| point.at);
+                let mut previous_at = 0;
+                points
+                    .into_iter()
+                    .map(|point| {
+                        let stage = Stage::new(point.at - previous_at, point.users);
+                        previous_at = point.at;
+                        stage
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let stages = self.to_stages();
+        if stages.is_empty() {
+            return Err("the load shape has no stages".into());
+        }
+        if let LoadShape::Custom(points) = self {
+            let mut times: Vec<u64> = points.iter().map(|point| point.at).collect();
+            times.sort_unstable();
+            times.dedup();
+            if times.len() != points.len() {
+                return Err("the load shape has more than one point at the same time".into());
+            }
+        }
+        if self.get_duration() == 0 {
+            return Err("the load shape has a duration of 0".into());
+        }
+        Ok(())
+    }
+
+    pub fn get_duration(&self) -> u64 {
+        self.to_stages().iter().map(|stage| stage.duration).sum()
+    }
+
+    pub fn get_max_users(&self) -> u32 {
+        self.to_stages()
+            .iter()
+            .map(|stage| stage.target)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // the number of users the test should have after elapsed, none if the shape has ended
+    pub fn get_users_at(&self, elapsed: Duration) -> Option<u32> {
+        let elapsed = elapsed.as_secs_f64();
+        let mut start = 0.0;
+        let mut previous_target = 0.0;
+        for stage in self.to_stages() {
+            let duration = stage.duration as f64;
+            let target = stage.target as f64;
+            if elapsed < start + duration {
+                let progress = (elapsed - start) / duration;
+                return Some(
+                    (previous_target + (target - previous_target) * progress).round() as u32,
+                );
+            }
+            start += duration;
+            previous_target = target;
+        }
+        None
+    }
+
+    // the share of the users from offset to offset + user_count of every stage.
+    // used by the master, so the workers together follow the shape
+    pub fn partition(&self, offset: u32, user_count: u32, total_user_count: u32) -> LoadShape {
+        if total_user_count == 0 {
+            return self.clone();
+        }
+        let share = |target: u32| {
+            let end = target as u64 * (offset + user_count) as u64 / total_user_count as u64;
+            let start = target as u64 * offset as u64 / total_user_count as u64;
+            (end - start) as u32
+        };
+        LoadShape::Stages(
+            self.to_stages()
+                .into_iter()
+                .map(|stage| Stage::new(stage.duration, share(stage.target)))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for LoadShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stages: Vec<String> = self
+            .to_stages()
+            .iter()
+            .map(|stage| format!("{}s->{}", stage.duration, stage.target))
+            .collect();
+        write!(f, "{}", stages.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users_at(load_shape: &LoadShape, seconds: f64) -> Option<u32> {
+        load_shape.get_users_at(Duration::from_secs_f64(seconds))
+    }
+
+    #[test]
+    fn stages_ramp_linearly_from_the_previous_target() {
+        let load_shape = LoadShape::Stages(vec![
+            Stage::new(10, 10),
+            Stage::new(10, 10),
+            Stage::new(5, 0),
+        ]);
+        assert_eq!(users_at(&load_shape, 0.0), Some(0));
+        assert_eq!(users_at(&load_shape, 5.0), Some(5));
+        assert_eq!(users_at(&load_shape, 15.0), Some(10));
+        assert_eq!(users_at(&load_shape, 22.5), Some(5));
+        assert_eq!(users_at(&load_shape, 25.0), None);
+        assert_eq!(load_shape.get_duration(), 25);
+        assert_eq!(load_shape.get_max_users(), 10);
+    }
+
+    #[test]
+    fn steps_and_spikes_jump_to_their_targets() {
+        let step = LoadShape::Step {
+            start: 2,
+            step: 3,
+            step_duration: 10,
+            steps: 3,
+        };
+        assert_eq!(users_at(&step, 0.0), Some(2));
+        assert_eq!(users_at(&step, 10.0), Some(5));
+        assert_eq!(users_at(&step, 29.9), Some(8));
+        assert_eq!(users_at(&step, 30.0), None);
+        assert_eq!(step.get_max_users(), 8);
+        let huge = LoadShape::Step {
+            start: u32::MAX - 1,
+            step: u32::MAX,
+            step_duration: 1,
+            steps: 3,
+        };
+        assert_eq!(huge.get_max_users(), u32::MAX);
+        let spike = LoadShape::Spike {
+            base: 1,
+            peak: 9,
+            before: 5,
+            spike_duration: 2,
+            after: 5,
+        };
+        assert_eq!(users_at(&spike, 4.9), Some(1));
+        assert_eq!(users_at(&spike, 5.0), Some(9));
+        assert_eq!(users_at(&spike, 7.0), Some(1));
+        assert_eq!(spike.get_duration(), 12);
+    }
+
+    #[test]
+    fn custom_points_are_sorted_and_unique() {
+        let custom = LoadShape::Custom(vec![Point::new(20, 0), Point::new(10, 10)]);
+        assert_eq!(
+            custom.to_stages(),
+            vec![Stage::new(10, 10), Stage::new(10, 0)]
+        );
+        assert!(custom.validate().is_ok());
+        let duplicated = LoadShape::Custom(vec![Point::new(10, 10), Point::new(10, 5)]);
+        assert!(duplicated.validate().is_err());
+        assert!(LoadShape::Stages(vec![]).validate().is_err());
+        assert!(LoadShape::Stages(vec![Stage::new(0, 10)])
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn uneven_partitions_add_up_to_the_shape() {
+        let load_shape = LoadShape::Stages(vec![Stage::new(10, 5), Stage::new(10, 7)]);
+        let partitions: Vec<Vec<Stage>> = (0..3)
+            .map(|offset| load_shape.partition(offset, 1, 3).to_stages())
+            .collect();
+        for (index, stage) in load_shape.to_stages().iter().enumerate() {
+            let targets: u32 = partitions.iter().map(|stages| stages[index].target).sum();
+            assert_eq!(targets, stage.target);
+            assert!(partitions
+                .iter()
+                .all(|stages| stages[index].duration == stage.duration));
+        }
+    }
+}
@@ -8,9 +8,21 @@ pub enum Status {
     Running,
     Stopped,
     Finished,
+    Failed(String),
     Error(String),
 }
 
+impl Status {
+    // exit code for command line tools, a failed test is not an error
+    pub fn get_exit_code(&self) -> i32 {
+        match self {
+            Status::Failed(_) => 1,
+            Status::Error(_) => 2,
+            _ => 0,
+        }
+    }
+}
+
 impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -19,6 +31,7 @@ impl fmt::Display for Status {
             Status::Running => write!(f, "RUNNING"),
             Status::Stopped => write!(f, "STOPPED"),
             Status::Finished => write!(f, "FINISHED"),
+            Status::Failed(ref msg) => write!(f, "FAILED [{}]", msg),
             Status::Error(ref msg) => write!(f, "Error [{}]", msg),
         }
     }
@@ -0,0 +1,252 @@
+use chrono::Utc;
+use rand::{seq::SliceRandom, Rng};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+const ALPHANUMERIC: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const ALPHA: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const NUMERIC: &str = "0123456789";
+const HEX: &str = "0123456789abcdef";
+
+/// The counters behind `{{counter()}}` and `{{global_counter()}}`.
+/// The global counter is shared by the users of a test, on a worker it counts the requests of that worker only.
+#[derive(Clone, Debug, Default)]
+pub struct Generators {
+    counter: Arc<AtomicU64>,        //PER USER
+    global_counter: Arc<AtomicU64>, //PER TEST
+}
+
+impl Generators {
+    pub fn new(global_counter: Arc<AtomicU64>) -> Generators {
+        Generators {
+            counter: Arc::new(AtomicU64::new(0)),
+            global_counter,
+        }
+    }
+
+    // the value of a generator like rand_int(1,10), none if the name or the arguments are unknown
+    fn generate(&self, name: &str, args: &[String]) -> Option<String> {
+        match (name, args.len()) {
+            ("rand_int", 2) => {
+                let min: i64 = args[0].parse().ok()?;
+                let max: i64 = args[1].parse().ok()?;
+                if min > max {
+                    return None;
+                }
+                Some(rand::thread_rng().gen_range(min..=max).to_string())
+            }
+            ("rand_float", 2) | ("rand_float", 3) => {
+                let min: f64 = args[0].parse().ok()?;
+                let max: f64 = args[1].parse().ok()?;
+                if min > max {
+                    return None;
+                }
+                let value = if min == max {
+                    min
+                } else {
+                    rand::thread_rng().gen_range(min..max)
+                };
+                match args.get(2) {
+                    Some(decimals) => {
+                        let decimals: usize = decimals.parse().ok()?;
+                        Some(format!("{:.*}", decimals, value))
+                    }
+                    None => Some(value.to_string()),
+                }
+            }
+            ("uuid", 0) => Some(uuid_v4()),
+            ("rand_string", 1) | ("rand_string", 2) => {
+                let length: usize = args[0].parse().ok()?;
+                let charset = match args.get(1).map(|charset| charset.as_str()) {
+                    None | Some("alphanumeric") => ALPHANUMERIC,
+                    Some("alpha") => ALPHA,
+                    Some("numeric") => NUMERIC,
+                    Some("hex") => HEX,
+                    Some(charset) => charset, //THE CHARACTERS TO PICK FROM
+                };
+                let charset: Vec<char> = charset.chars().collect();
+                let mut rng = rand::thread_rng();
+                (0..length)
+                    .map(|_| charset.choose(&mut rng).copied())
+                    .collect()
+            }
+            ("timestamp", 0) => Some(Utc::now().timestamp().to_string()),
+            ("timestamp", 1) => {
+                let now = Utc::now();
+                match args[0].as_str() {
+                    "unix" => Some(now.timestamp().to_string()),
+                    "unix_ms" => Some(now.timestamp_millis().to_string()),
+                    "rfc3339" | "iso8601" => Some(now.to_rfc3339()),
+                    "rfc2822" => Some(now.to_rfc2822()),
+                    format => {
+                        //strftime. an invalid format fails to display instead of failing to parse
+                        let mut value = String::new();
+                        write!(value, "{}", now.format(format)).ok()?;
+                        Some(value)
+                    }
+                }
+            }
+            ("counter", 0) => Some((self.counter.fetch_add(1, Ordering::Relaxed) + 1).to_string()),
+            ("global_counter", 0) => {
+                Some((self.global_counter.fetch_add(1, Ordering::Relaxed) + 1).to_string())
+            }
+            ("pick", count) if count > 0 => args.choose(&mut rand::thread_rng()).cloned(),
+            _ => None,
+        }
+    }
+}
+
+fn uuid_v4() -> String {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40; //VERSION 4
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; //RFC 4122 VARIANT
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn finish_arg(arg: &str, quoted: bool) -> String {
+    if quoted {
+        arg.to_string()
+    } else {
+        arg.trim().to_string()
+    }
+}
+
+// "rand_int(1, 10)" -> ("rand_int", ["1", "10"]). arguments may be quoted to keep commas and spaces
+fn parse_call(expression: &str) -> Option<(&str, Vec<String>)> {
+    let open = expression.find('(')?;
+    let inner = expression[open + 1..].strip_suffix(')')?;
+    let name = expression[..open].trim();
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut quoted = false;
+    for c in inner.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                quoted = true;
+            }
+            (None, ',') => {
+                args.push(finish_arg(&current, quoted));
+                current.clear();
+                quoted = false;
+            }
+            //whitespace around a quoted argument is dropped
+            (None, c) if c.is_whitespace() && (quoted || current.is_empty()) => {}
+            (None, c) => current.push(c),
+        }
+    }
+    if quoted || !current.trim().is_empty() || !args.is_empty() {
+        args.push(finish_arg(&current, quoted));
+    }
+    Some((name, args))
+}
+
+// replaces every {{variable}} with the value of the variable and every {{generator(args)}} with a generated value.
+// unknown variables and generators are left as they are, so a mistake shows up in the logs instead of sending an empty value
+pub fn render(
+    template: &str,
+    variables: &HashMap<String, String>,
+    generators: &Generators,
+) -> String {
+    if !template.contains("{{") {
+        return template.to_string();
+    }
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + end;
+        let expression = rest[start + 2..end].trim();
+        rendered.push_str(&rest[..start]);
+        let value = match variables.get(expression) {
+            Some(value) => Some(value.clone()),
+            None => {
+                parse_call(expression).and_then(|(name, args)| generators.generate(name, &args))
+            }
+        };
+        match value {
+            Some(value) => rendered.push_str(&value),
+            None => rendered.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_with(template: &str, variables: &[(&str, &str)]) -> String {
+        let variables = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        render(template, &variables, &Generators::default())
+    }
+
+    #[test]
+    fn renders_variables() {
+        assert_eq!(render_with("/users/{{ id }}", &[("id", "7")]), "/users/7");
+        assert_eq!(render_with("{{a}}-{{b}}", &[("a", "x"), ("b", "y")]), "x-y");
+    }
+
+    #[test]
+    fn unknown_and_unclosed_expressions_are_left_as_they_are() {
+        assert_eq!(render_with("{{missing}}", &[]), "{{missing}}");
+        assert_eq!(render_with("{{id", &[("id", "7")]), "{{id");
+        assert_eq!(render_with("{{id}} {{", &[("id", "7")]), "7 {{");
+        assert_eq!(render_with("{}", &[]), "{}");
+    }
+
+    #[test]
+    fn renders_generators() {
+        assert_eq!(render_with("{{counter()}},{{counter()}}", &[]), "1,2");
+        assert_eq!(render_with("{{rand_int(3, 3)}}", &[]), "3");
+        assert_eq!(render_with("{{rand_float(1.5, 1.5, 2)}}", &[]), "1.50");
+        assert_eq!(render_with("{{rand_int(9, 1)}}", &[]), "{{rand_int(9, 1)}}");
+    }
+
+    #[test]
+    fn quoted_arguments_keep_commas_and_spaces() {
+        assert_eq!(render_with("{{pick(' a, b ')}}", &[]), " a, b ");
+        assert_eq!(render_with("{{rand_string(3, \",\")}}", &[]), ",,,");
+        assert_eq!(
+            parse_call("pick( 'x' , y , \"z, w\" )"),
+            Some((
+                "pick",
+                vec![String::from("x"), String::from("y"), String::from("z, w")]
+            ))
+        );
+        assert_eq!(parse_call("uuid()"), Some(("uuid", vec![])));
+        assert_eq!(parse_call("pick('')"), Some(("pick", vec![String::new()])));
+    }
+
+    #[test]
+    fn uuid_is_a_version_4_uuid() {
+        let uuid = uuid_v4();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"));
+    }
+}
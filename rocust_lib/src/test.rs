@@ -1,7 +1,24 @@
-use crate::{EndPoint, HasResults, LogType, Logger, Results, Runnable, Status, SentResults};
+use crate::{
+    arrival::ArrivalRate,
+    auth::{Auth, Authenticator, TokenCache},
+    client::Clients,
+    cookies::{CookieConfig, CookieJar},
+    endpoint,
+    errors::ErrorKind,
+    feeder::{Feeder, FeederStrategy},
+    shape::LoadShape,
+    report::{self, html},
+    results::{format_optional_response_time, format_rate, format_response_time},
+    template::Generators,
+    think_time::ThinkTime,
+    user_class::UserClass,
+    threshold::ThresholdBreach,
+    ClientConfig, EndPoint, HasResults, History, LogType, Logger, Results, Runnable, Scenario,
+    SentResults, Status, Threshold,
+};
 use async_trait::async_trait;
 use parking_lot::RwLock;
-use prettytable::{row, Table};
+use prettytable::{row, Row, Table};
 use serde::{
     de::{MapAccess, Visitor},
     ser::SerializeStruct,
@@ -11,15 +28,21 @@ use std::{
     collections::HashMap,
     error::Error,
     fmt,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::{Duration, Instant},
 };
-use tokio::{fs, select};
+use tokio::{fs, select, sync::mpsc, task::JoinHandle};
 use tokio_util::sync::CancellationToken;
 
 use user::User;
 pub mod user;
 
+pub const DEFAULT_CURRENT_WINDOW: u64 = 10;
+pub const LOAD_SHAPE_TICK: u64 = 100; //MILLISECONDS BETWEEN TWO ADJUSTMENTS OF THE USERS TO THE LOAD SHAPE
+
 #[derive(Clone, Debug)]
 pub struct Test {
     id: String,
@@ -27,19 +50,43 @@ pub struct Test {
     background_token: Arc<Mutex<CancellationToken>>,
     user_count: u32,
     run_time: Option<u64>,
-    sleep: (u64, u64),
+    spawn_rate: Option<f64>, //USERS PER SECOND, ALL USERS ARE SPAWNED AT ONCE IF NONE
+    ramp_down: Option<u64>,  //SECONDS TO STOP THE USERS IN AFTER THE RUN TIME, ALL AT ONCE IF NONE
+    spawn_token: Arc<Mutex<CancellationToken>>, //CANCELLED WHEN THE TEST IS STOPPED OR FINISHED
+    load_shape: Arc<Option<LoadShape>>, //TAKES OVER THE USER COUNT AND THE SPAWN RATE IF SET
+    arrival_rate: Arc<Option<ArrivalRate>>, //OPEN MODEL, TAKES OVER THE USER COUNT AND THE SPAWN RATE IF SET
+    expected_interval: Option<u64>, //MILLISECONDS BETWEEN TWO REQUESTS OF A USER, ENABLES THE COORDINATED OMISSION CORRECTION
+    sleep: (u64, u64), //SECONDS, USED IF THERE IS NO THINK TIME
+    think_time: Option<ThinkTime>,
     host: Arc<String>,
     endpoints: Arc<Vec<EndPoint>>,
     global_headers: Arc<Option<HashMap<String, String>>>,
     results: Arc<RwLock<Results>>, //AGGREGATED RESULTS
+    history: Arc<RwLock<History>>,
+    current_window: u64, //SECONDS OF THE TRAILING WINDOW FOR THE CURRENT REQUESTS PER SECOND
+    thresholds: Arc<Vec<Threshold>>,
+    client_config: Arc<ClientConfig>,
+    cookie_config: Arc<Option<CookieConfig>>, //NONE DISABLES THE COOKIE JARS OF THE USERS
+    auth: Arc<Option<Auth>>,
+    token_cache: Arc<TokenCache>, //THE SHARED OAUTH2 TOKEN, NOT SENT TO WORKERS
+    scenarios: Arc<Vec<Scenario>>,
+    user_classes: Arc<Vec<UserClass>>, //THE ENDPOINTS AND SCENARIOS OF THE TEST ARE NOT USED IF THERE ARE USER CLASSES
+    class_users: Arc<RwLock<Vec<u32>>>, //USERS CREATED PER USER CLASS, NOT SENT TO WORKERS
+    feeders: Arc<Vec<Feeder>>,
+    global_counter: Arc<AtomicU64>, //BEHIND {{global_counter()}}, NOT SENT TO WORKERS
     start_timestamp: Arc<RwLock<Option<Instant>>>,
     end_timestamp: Arc<RwLock<Option<Instant>>>,
     users: Arc<RwLock<Vec<User>>>,
     logger: Arc<Logger>,
     print_stats_to_console: Arc<bool>,
+    html_report_path: Option<String>, //NOT SENT TO WORKERS
+    csv_report_path: Option<String>, //NOT SENT TO WORKERS
+    history_report_path: Option<String>, //JSON LINES, NOT SENT TO WORKERS
+    junit_report_path: Option<String>, //NOT SENT TO WORKERS
 }
 
 impl Test {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: String,
         user_count: u32,
@@ -58,16 +105,39 @@ impl Test {
             background_token: Arc::new(Mutex::new(CancellationToken::new())),
             user_count,
             run_time,
+            spawn_rate: None,
+            ramp_down: None,
+            spawn_token: Arc::new(Mutex::new(CancellationToken::new())),
+            load_shape: Arc::new(None),
+            arrival_rate: Arc::new(None),
+            expected_interval: None,
             sleep,
+            think_time: None,
             host: Arc::new(host),
             endpoints: Arc::new(endpoints),
             global_headers: Arc::new(global_headers),
             results: Arc::new(RwLock::new(Results::new())),
+            history: Arc::new(RwLock::new(History::default())),
+            current_window: DEFAULT_CURRENT_WINDOW,
+            thresholds: Arc::new(Vec::new()),
+            client_config: Arc::new(ClientConfig::new()),
+            cookie_config: Arc::new(None),
+            auth: Arc::new(None),
+            token_cache: Arc::new(TokenCache::new()),
+            scenarios: Arc::new(Vec::new()),
+            user_classes: Arc::new(Vec::new()),
+            class_users: Arc::new(RwLock::new(Vec::new())),
+            feeders: Arc::new(Vec::new()),
+            global_counter: Arc::new(AtomicU64::new(0)),
             start_timestamp: Arc::new(RwLock::new(None)),
             end_timestamp: Arc::new(RwLock::new(None)),
             users: Arc::new(RwLock::new(Vec::new())),
             logger: Arc::new(Logger::new(logfile_path, print_log_to_console)),
             print_stats_to_console: Arc::new(print_stats_to_console),
+            html_report_path: None,
+            csv_report_path: None,
+            history_report_path: None,
+            junit_report_path: None,
         }
     }
 
@@ -114,10 +184,21 @@ impl Test {
             ) {
                 self.calculate_requests_per_second(&elapsed);
                 self.calculate_failed_requests_per_second(&elapsed);
+                self.calculate_current_requests_per_second(&elapsed, &self.get_current_window());
+            }
+            //calculate percentiles
+            self.calculate_percentiles();
+            //record history
+            self.record_history(self.get_active_users_count());
+            //abort on breached thresholds
+            if self.should_abort() {
+                self.finish();
             }
             //print stats
             if *self.print_stats_to_console {
+                self.print_users(self.get_active_users_count());
                 self.print_stats();
+                self.print_errors();
             }
             //log
             let _ = self.logger.flush_buffer().await;
@@ -125,18 +206,79 @@ impl Test {
         }
     }
 
-    pub fn create_user(&self, id: String) -> User {
-        let user = User::new(
+    pub fn create_user(&self, id: String) -> Result<User, Box<dyn Error>> {
+        let user_class = self.select_user_class();
+        let (think_time, endpoints, scenarios, global_headers) = match user_class {
+            Some(user_class) => (
+                user_class
+                    .get_think_time()
+                    .clone()
+                    .unwrap_or_else(|| self.get_think_time()),
+                user_class.get_endpoints().clone(),
+                user_class.get_scenarios().clone(),
+                Arc::new(user_class.merge_headers(&self.global_headers)),
+            ),
+            None => (
+                self.get_think_time(),
+                self.endpoints.clone(),
+                self.scenarios.clone(),
+                self.global_headers.clone(),
+            ),
+        };
+        let mut user = User::new(
             id,
-            self.sleep,
+            self.create_clients()?,
+            Generators::new(self.global_counter.clone()),
+            self.create_authenticator(),
+            think_time,
             self.host.clone(),
-            self.endpoints.clone(),
-            self.global_headers.clone(),
+            endpoints,
+            scenarios,
+            self.feeders.clone(),
+            global_headers,
             self.results.clone(),
             self.logger.clone(),
         );
+        user.set_expected_interval(
+            self.expected_interval
+                .map(|expected_interval| expected_interval.saturating_mul(1000)),
+        );
+        if let Some(user_class) = user_class {
+            user.set_class_results(Some(user_class.get_results().clone()));
+        }
+        if let Err(feeder) = user.take_unique_row() {
+            return Err(format!("unique feeder [{}] ran out of rows", feeder).into());
+        }
         self.users.write().push(user.clone());
-        user
+        Ok(user)
+    }
+
+    // the class that is furthest behind its share of the users created so far, none if the test has no user classes.
+    // after as many users as the total weight, every class has exactly as many users as its weight
+    fn select_user_class(&self) -> Option<&UserClass> {
+        let total_weight: u64 = self
+            .user_classes
+            .iter()
+            .map(|user_class| user_class.get_weight() as u64)
+            .sum();
+        if total_weight == 0 {
+            return None;
+        }
+        let mut class_users = self.class_users.write();
+        class_users.resize(self.user_classes.len(), 0);
+        let created_users = class_users.iter().map(|users| *users as u64).sum::<u64>() + 1;
+        let mut selected: Option<(usize, f64)> = None;
+        for (index, user_class) in self.user_classes.iter().enumerate() {
+            let deficit = user_class.get_weight() as f64 * created_users as f64
+                / total_weight as f64
+                - class_users[index] as f64;
+            if !matches!(selected, Some((_, max_deficit)) if max_deficit >= deficit) {
+                selected = Some((index, deficit));
+            }
+        }
+        let (index, _) = selected?;
+        class_users[index] += 1;
+        self.user_classes.get(index)
     }
 
     pub fn stop_a_user(&self, user_id: usize) -> Result<(), String> {
@@ -182,58 +324,525 @@ impl Test {
         self.run_time = run_time;
     }
 
+    pub fn set_think_time(&mut self, think_time: Option<ThinkTime>) {
+        self.think_time = think_time;
+    }
+
+    pub fn set_spawn_rate(&mut self, spawn_rate: Option<f64>) {
+        self.spawn_rate = spawn_rate;
+    }
+
+    pub fn set_ramp_down(&mut self, ramp_down: Option<u64>) {
+        self.ramp_down = ramp_down;
+    }
+
+    // the test has as many users as the peak of the shape, so the master splits the users and the feeders by the peak
+    pub fn set_load_shape(&mut self, load_shape: Option<LoadShape>) {
+        if let Some(ref load_shape) = load_shape {
+            self.user_count = load_shape.get_max_users();
+        }
+        self.load_shape = Arc::new(load_shape);
+    }
+
+    // the test has as many users as the maximum of the pool, so the master splits the users and the feeders by the maximum
+    pub fn set_arrival_rate(&mut self, arrival_rate: Option<ArrivalRate>) {
+        if let Some(ref arrival_rate) = arrival_rate {
+            self.user_count = arrival_rate.max_users;
+        }
+        self.arrival_rate = Arc::new(arrival_rate);
+    }
+
+    // the latencies are also recorded as if every user had sent a request every expected_interval milliseconds,
+    // so a slow response accounts for the requests that should have been sent while waiting for it
+    pub fn set_expected_interval(&mut self, expected_interval: Option<u64>) {
+        self.expected_interval = expected_interval;
+    }
+
+    pub fn partition_arrival_rate(&mut self, offset: u32, user_count: u32, total_user_count: u32) {
+        if let Some(ref arrival_rate) = *self.arrival_rate {
+            self.arrival_rate = Arc::new(Some(arrival_rate.partition(
+                offset,
+                user_count,
+                total_user_count,
+            )));
+        }
+    }
+
+    // keeps the share of the users from offset to offset + user_count of every stage of the load shape
+    pub fn partition_load_shape(&mut self, offset: u32, user_count: u32, total_user_count: u32) {
+        if let Some(ref load_shape) = *self.load_shape {
+            self.load_shape = Arc::new(Some(load_shape.partition(
+                offset,
+                user_count,
+                total_user_count,
+            )));
+        }
+    }
+
     pub fn set_user_count(&mut self, user_count: u32) {
         self.user_count = user_count;
     }
 
-    pub fn print_stats(&self) {
-        let mut table = Table::new();
-        table.add_row(row![
+    pub fn set_html_report_path(&mut self, html_report_path: Option<String>) {
+        self.html_report_path = html_report_path;
+    }
+
+    pub fn set_csv_report_path(&mut self, csv_report_path: Option<String>) {
+        self.csv_report_path = csv_report_path;
+    }
+
+    pub fn set_history_report_path(&mut self, history_report_path: Option<String>) {
+        self.history_report_path = history_report_path;
+    }
+
+    pub fn set_junit_report_path(&mut self, junit_report_path: Option<String>) {
+        self.junit_report_path = junit_report_path;
+    }
+
+    pub fn set_thresholds(&mut self, thresholds: Vec<Threshold>) {
+        self.thresholds = Arc::new(thresholds);
+    }
+
+    pub fn set_scenarios(&mut self, scenarios: Vec<Scenario>) {
+        self.scenarios = Arc::new(scenarios);
+    }
+
+    pub fn set_user_classes(&mut self, user_classes: Vec<UserClass>) {
+        self.user_classes = Arc::new(user_classes);
+    }
+
+    // replaces the weights of the user classes with their number of users among the users from offset to offset + user_count.
+    // used by the master, so the users of all workers together are split like the weights of the test
+    pub fn partition_user_classes(&mut self, offset: u32, user_count: u32) {
+        let total_weight: u64 = self
+            .user_classes
+            .iter()
+            .map(|user_class| user_class.get_weight() as u64)
+            .sum();
+        if user_count == 0 || total_weight == 0 {
+            return;
+        }
+        //users of the classes up to a cumulative weight among the users of the partition
+        let partitioned_users = |cumulative_weight: u64| {
+            let end = (offset + user_count) as u64 * cumulative_weight / total_weight;
+            let start = offset as u64 * cumulative_weight / total_weight;
+            end - start
+        };
+        let mut cumulative_weight = 0;
+        let mut user_classes = Vec::new();
+        for user_class in self.user_classes.iter() {
+            let previous_users = partitioned_users(cumulative_weight);
+            cumulative_weight += user_class.get_weight() as u64;
+            let mut user_class = user_class.clone();
+            user_class.set_weight((partitioned_users(cumulative_weight) - previous_users) as u32);
+            user_classes.push(user_class);
+        }
+        self.user_classes = Arc::new(user_classes);
+    }
+
+    pub fn set_feeders(&mut self, feeders: Vec<Feeder>) {
+        self.feeders = Arc::new(feeders);
+    }
+
+    // keeps the rows of sequential and unique feeders that belong to the users from offset to offset + user_count.
+    // used by the master, so no row is used by more than one worker
+    pub fn partition_feeders(&mut self, offset: u32, user_count: u32, total_user_count: u32) {
+        let feeders = self
+            .feeders
+            .iter()
+            .map(|feeder| feeder.partition(offset, user_count, total_user_count))
+            .collect();
+        self.feeders = Arc::new(feeders);
+    }
+
+    // a worker without rows of a sequential feeder would fail to validate,
+    // so the master needs a row for every user
+    pub fn validate_feeder_partitions(&self) -> Result<(), Box<dyn Error>> {
+        for feeder in self
+            .feeders
+            .iter()
+            .filter(|feeder| feeder.get_strategy().is_partitioned())
+        {
+            if feeder.get_rows().len() < self.user_count as usize {
+                return Err(format!(
+                    "feeder [{}] has {} rows for {} users, the workers need a row for every user",
+                    feeder.get_name(),
+                    feeder.get_rows().len(),
+                    self.user_count
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_client_config(&mut self, client_config: ClientConfig) {
+        self.client_config = Arc::new(client_config);
+    }
+
+    pub fn set_cookie_config(&mut self, cookie_config: Option<CookieConfig>) {
+        self.cookie_config = Arc::new(cookie_config);
+    }
+
+    pub fn set_auth(&mut self, auth: Option<Auth>) {
+        self.auth = Arc::new(auth);
+    }
+
+    pub fn set_current_window(&mut self, current_window: u64) {
+        self.current_window = current_window;
+    }
+
+    pub fn get_total_weight(&self) -> u64 {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.get_weight() as u64)
+            .sum()
+    }
+
+    // the share of the requests the endpoint should get according to its weight
+    pub fn get_expected_mix(&self, endpoint: &EndPoint) -> f64 {
+        let total_weight = self.get_total_weight();
+        if total_weight == 0 {
+            return 0.0;
+        }
+        endpoint.get_weight() as f64 / total_weight as f64
+    }
+
+    // the share of the requests of the endpoints of the test the endpoint actually got.
+    // requests of scenarios and user classes are not part of the mix
+    pub fn get_actual_mix(&self, endpoint: &EndPoint) -> f64 {
+        endpoint::get_actual_mix(endpoint, self.endpoints.iter())
+    }
+
+    fn create_stats_row(method: &str, url: &str, mix: [String; 3], results: &Results) -> Row {
+        let [weight, expected_mix, actual_mix] = mix;
+        row![
+            method,
+            url,
+            weight,
+            expected_mix,
+            actual_mix,
+            results.total_requests,
+            results.total_failed_requests,
+            results.total_connection_errors,
+            results.requests_per_second,
+            results.failed_requests_per_second,
+            results.current_requests_per_second,
+            format_rate(results.current_failure_rate),
+            format_response_time(results.total_response_time),
+            format_response_time(results.average_response_time),
+            format_optional_response_time(results.min_response_time),
+            format_response_time(results.median_response_time),
+            format_response_time(results.percentile_90_response_time),
+            format_response_time(results.percentile_95_response_time),
+            format_response_time(results.percentile_99_response_time),
+            format_response_time(results.percentile_999_response_time),
+            format_optional_response_time(results.max_response_time),
+        ]
+    }
+
+    fn create_stats_header() -> Row {
+        row![
             "METH",
             "URL",
+            "WEIGHT",
+            "EXP MIX %",
+            "MIX %",
             "TOTAL REQ",
             "REQ FAILED",
             "CONN ERR",
             "REQ/S",
             "FAILED REQ/S",
-            "TOTAL RES TIME",
-            "AVG RES TIME",
-            "MIN RES TIME",
-            "MAX RES TIME",
-        ]);
+            "CUR REQ/S",
+            "CUR FAILED %",
+            "TOTAL RES TIME (ms)",
+            "AVG RES TIME (ms)",
+            "MIN RES TIME (ms)",
+            "MED RES TIME (ms)",
+            "P90 RES TIME (ms)",
+            "P95 RES TIME (ms)",
+            "P99 RES TIME (ms)",
+            "P99.9 RES TIME (ms)",
+            "MAX RES TIME (ms)",
+        ]
+    }
+
+    pub fn print_stats(&self) {
+        let mut table = Table::new();
+        table.add_row(Test::create_stats_header());
         for endpoint in self.endpoints.iter() {
-            let results = endpoint.get_results().read();
-            table.add_row(row![
-                endpoint.get_method(),
+            let mix = [
+                endpoint.get_weight().to_string(),
+                format_rate(self.get_expected_mix(endpoint)),
+                format_rate(self.get_actual_mix(endpoint)),
+            ];
+            table.add_row(Test::create_stats_row(
+                &endpoint.get_method().to_string(),
                 endpoint.get_url(),
-                results.total_requests,
-                results.total_failed_requests,
-                results.total_connection_errors,
-                results.requests_per_second,
-                results.failed_requests_per_second,
-                results.total_response_time,
-                results.average_response_time,
-                results.min_response_time,
-                results.max_response_time,
-            ]);
+                mix,
+                &endpoint.get_results().read(),
+            ));
         }
-        let results = self.results.read();
+        let mix = [
+            self.get_total_weight().to_string(),
+            String::from(" "),
+            String::from(" "),
+        ];
+        table.add_row(Test::create_stats_row(" ", "AGR", mix, &self.results.read()));
+        table.printstd();
+        if self.expected_interval.is_some() {
+            self.print_corrected_stats();
+        }
+        self.print_scenarios();
+        self.print_user_classes();
+    }
+
+    fn create_corrected_stats_row(method: &str, url: &str, results: &Results) -> Row {
+        row![
+            method,
+            url,
+            format_response_time(results.median_response_time),
+            format_response_time(results.corrected_median_response_time),
+            format_response_time(results.percentile_90_response_time),
+            format_response_time(results.corrected_percentile_90_response_time),
+            format_response_time(results.percentile_95_response_time),
+            format_response_time(results.corrected_percentile_95_response_time),
+            format_response_time(results.percentile_99_response_time),
+            format_response_time(results.corrected_percentile_99_response_time),
+            format_response_time(results.percentile_999_response_time),
+            format_response_time(results.corrected_percentile_999_response_time),
+        ]
+    }
+
+    // raw and corrected percentiles side by side
+    fn print_corrected_stats(&self) {
+        let mut table = Table::new();
         table.add_row(row![
+            "METH",
+            "URL",
+            "MED (ms)",
+            "MED CORR (ms)",
+            "P90 (ms)",
+            "P90 CORR (ms)",
+            "P95 (ms)",
+            "P95 CORR (ms)",
+            "P99 (ms)",
+            "P99 CORR (ms)",
+            "P99.9 (ms)",
+            "P99.9 CORR (ms)",
+        ]);
+        for endpoint in self.endpoints.iter() {
+            table.add_row(Test::create_corrected_stats_row(
+                &endpoint.get_method().to_string(),
+                endpoint.get_url(),
+                &endpoint.get_results().read(),
+            ));
+        }
+        table.add_row(Test::create_corrected_stats_row(
             " ",
             "AGR",
-            results.total_requests,
-            results.total_failed_requests,
-            results.total_connection_errors,
-            results.requests_per_second,
-            results.failed_requests_per_second,
-            results.total_response_time,
-            results.average_response_time,
-            results.min_response_time,
-            results.max_response_time,
-        ]);
+            &self.results.read(),
+        ));
         table.printstd();
     }
 
+    // the share of the scenario runs the scenario should get according to its weight
+    pub fn get_expected_scenario_mix(&self, scenario: &Scenario) -> f64 {
+        let total_weight: u64 = self
+            .scenarios
+            .iter()
+            .map(|scenario| scenario.get_weight() as u64)
+            .sum();
+        if total_weight == 0 {
+            return 0.0;
+        }
+        scenario.get_weight() as f64 / total_weight as f64
+    }
+
+    // the share of the scenario runs the scenario actually got
+    pub fn get_actual_scenario_mix(&self, scenario: &Scenario) -> f64 {
+        let total_runs: u64 = self
+            .scenarios
+            .iter()
+            .map(|scenario| scenario.get_results().read().total_requests)
+            .sum();
+        if total_runs == 0 {
+            return 0.0;
+        }
+        scenario.get_results().read().total_requests as f64 / total_runs as f64
+    }
+
+    // scenario rows hold the transaction results, the rows below them the results of their steps
+    pub fn print_scenarios(&self) {
+        if self.scenarios.is_empty() {
+            return;
+        }
+        let mut table = Table::new();
+        table.add_row(Test::create_stats_header());
+        for scenario in self.scenarios.iter() {
+            let mix = [
+                scenario.get_weight().to_string(),
+                format_rate(self.get_expected_scenario_mix(scenario)),
+                format_rate(self.get_actual_scenario_mix(scenario)),
+            ];
+            table.add_row(Test::create_stats_row(
+                "SCN",
+                scenario.get_name(),
+                mix,
+                &scenario.get_results().read(),
+            ));
+            for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                let mix = [String::from(" "), String::from(" "), String::from(" ")];
+                table.add_row(Test::create_stats_row(
+                    &endpoint.get_method().to_string(),
+                    &format!("{} {}", scenario.get_step_id(index), endpoint.get_url()),
+                    mix,
+                    &endpoint.get_results().read(),
+                ));
+            }
+        }
+        table.printstd();
+    }
+
+    // the share of the users the user class should get according to its weight
+    pub fn get_expected_user_class_mix(&self, user_class: &UserClass) -> f64 {
+        let total_weight: u64 = self
+            .user_classes
+            .iter()
+            .map(|user_class| user_class.get_weight() as u64)
+            .sum();
+        if total_weight == 0 {
+            return 0.0;
+        }
+        user_class.get_weight() as f64 / total_weight as f64
+    }
+
+    // the share of the requests the user class actually got
+    pub fn get_actual_user_class_mix(&self, user_class: &UserClass) -> f64 {
+        let total_requests = self.results.read().total_requests;
+        if total_requests == 0 {
+            return 0.0;
+        }
+        user_class.get_results().read().total_requests as f64 / total_requests as f64
+    }
+
+    // class rows hold the results of all users of the class, the rows above them the results of its endpoints and scenarios.
+    // the mix of a class is its weight, its share of the users and its share of the requests
+    pub fn print_user_classes(&self) {
+        if self.user_classes.is_empty() {
+            return;
+        }
+        let mut table = Table::new();
+        table.add_row(Test::create_stats_header());
+        for user_class in self.user_classes.iter() {
+            for endpoint in user_class.get_endpoints().iter() {
+                let mix = [
+                    endpoint.get_weight().to_string(),
+                    format_rate(user_class.get_expected_mix(endpoint)),
+                    format_rate(user_class.get_actual_mix(endpoint)),
+                ];
+                table.add_row(Test::create_stats_row(
+                    &endpoint.get_method().to_string(),
+                    &user_class.get_endpoint_id(endpoint),
+                    mix,
+                    &endpoint.get_results().read(),
+                ));
+            }
+            for scenario in user_class.get_scenarios().iter() {
+                let mix = [
+                    scenario.get_weight().to_string(),
+                    String::from(" "),
+                    String::from(" "),
+                ];
+                table.add_row(Test::create_stats_row(
+                    "SCN",
+                    &user_class.get_scenario_id(scenario),
+                    mix,
+                    &scenario.get_results().read(),
+                ));
+                for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                    let mix = [String::from(" "), String::from(" "), String::from(" ")];
+                    table.add_row(Test::create_stats_row(
+                        &endpoint.get_method().to_string(),
+                        &format!(
+                            "{} {}",
+                            user_class.get_step_id(scenario, index),
+                            endpoint.get_url()
+                        ),
+                        mix,
+                        &endpoint.get_results().read(),
+                    ));
+                }
+            }
+            let mix = [
+                user_class.get_weight().to_string(),
+                format_rate(self.get_expected_user_class_mix(user_class)),
+                format_rate(self.get_actual_user_class_mix(user_class)),
+            ];
+            table.add_row(Test::create_stats_row(
+                "CLS",
+                user_class.get_name(),
+                mix,
+                &user_class.get_results().read(),
+            ));
+        }
+        table.printstd();
+    }
+
+    fn add_error_rows(table: &mut Table, method: &str, url: &str, results: &Results) {
+        for (status_code, count) in results.errors.get_sorted_status_codes() {
+            table.add_row(row![method, url, format!("STATUS {}", status_code), count]);
+        }
+        for (kind, count) in results.errors.get_sorted_kinds() {
+            table.add_row(row![method, url, kind, count]);
+        }
+    }
+
+    // prints nothing if there are no errors
+    pub fn print_errors(&self) {
+        let results = self.results.read();
+        if results.errors.is_empty() {
+            return;
+        }
+        let mut table = Table::new();
+        table.add_row(row!["METH", "URL", "ERROR", "COUNT"]);
+        for endpoint in self.endpoints.iter() {
+            Test::add_error_rows(
+                &mut table,
+                &endpoint.get_method().to_string(),
+                endpoint.get_url(),
+                &endpoint.get_results().read(),
+            );
+        }
+        for scenario in self.scenarios.iter() {
+            Test::add_error_rows(
+                &mut table,
+                "SCN",
+                scenario.get_name(),
+                &scenario.get_results().read(),
+            );
+            for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                Test::add_error_rows(
+                    &mut table,
+                    &endpoint.get_method().to_string(),
+                    &format!("{} {}", scenario.get_step_id(index), endpoint.get_url()),
+                    &endpoint.get_results().read(),
+                );
+            }
+        }
+        Test::add_error_rows(&mut table, " ", "AGR", &results);
+        table.printstd();
+        if results.errors.samples.is_empty() {
+            return;
+        }
+        let mut samples = Table::new();
+        samples.add_row(row!["ERROR", "COUNT", "SAMPLE MESSAGE"]);
+        for sample in results.errors.samples.iter() {
+            samples.add_row(row![sample.kind, sample.count, sample.message]);
+        }
+        samples.printstd();
+    }
+
     pub fn get_elapsed_time(&self) -> Option<Duration> {
         match (*self.start_timestamp.read(), *self.end_timestamp.read()) {
             (Some(start), Some(end)) => Some(end.duration_since(start)),
@@ -257,6 +866,278 @@ impl Test {
         self.user_count
     }
 
+    fn spawn_user(&self, user_id: u32) -> Option<JoinHandle<()>> {
+        self.logger
+            .log_buffered(LogType::Info, &format!("Spawning user: [{}]", user_id));
+        let mut user = match self.create_user(user_id.to_string()) {
+            Ok(user) => user,
+            Err(e) => {
+                self.logger.log_buffered(
+                    LogType::Error,
+                    &format!("Error while creating user [{}]: {}", user_id, e),
+                );
+                return None;
+            }
+        };
+        Some(tokio::spawn(async move {
+            user.run().await;
+        }))
+    }
+
+    // spawns and stops users every tick to match the shape, until the shape ends or the test is stopped.
+    // finished users are restarted before new users are created, and no more users than the peak of the shape
+    // are created. once a feeder runs out of rows no users are spawned anymore, the shape only stops users
+    async fn follow_load_shape(
+        &self,
+        load_shape: &LoadShape,
+        user_join_handles: &mut Vec<JoinHandle<()>>,
+    ) {
+        let spawn_token = self.spawn_token.lock().unwrap().clone();
+        let max_users = load_shape.get_max_users() as usize;
+        let exhausted = Arc::new(AtomicBool::new(false));
+        //INDEX OF THE USER -> ITS LAST RUN
+        let mut shape_join_handles: HashMap<usize, JoinHandle<()>> = HashMap::new();
+        let start = Instant::now();
+        while let Some(target) = load_shape.get_users_at(start.elapsed()) {
+            //spawned users that have not started running yet count as active
+            let active_users: Vec<usize> = self
+                .users
+                .read()
+                .iter()
+                .enumerate()
+                .filter(|(_, user)| {
+                    matches!(user.get_status(), Status::Created | Status::Running)
+                })
+                .map(|(index, _)| index)
+                .collect();
+            let active_users_count = active_users.len() as u32;
+            if active_users_count < target {
+                for _ in active_users_count..target {
+                    if exhausted.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    //a finished user can only be restarted once its last run has ended
+                    let finished_user = self
+                        .users
+                        .read()
+                        .iter()
+                        .enumerate()
+                        .find(|(index, user)| {
+                            matches!(user.get_status(), Status::Finished)
+                                && matches!(
+                                    shape_join_handles.get(index),
+                                    Some(join_handle) if join_handle.is_finished()
+                                )
+                        })
+                        .map(|(index, _)| index);
+                    let (index, user) = match finished_user {
+                        Some(index) => {
+                            let user = self.users.read()[index].clone();
+                            self.logger.log_buffered(
+                                LogType::Info,
+                                &format!("Restarting user: [{}]", user.get_id()),
+                            );
+                            user.reset();
+                            (index, user)
+                        }
+                        None => {
+                            let index = self.users.read().len();
+                            if index >= max_users {
+                                break;
+                            }
+                            match self.create_logged_user(index) {
+                                Some(user) => (index, user),
+                                //most likely a unique feeder that ran out of rows
+                                None => {
+                                    exhausted.store(true, Ordering::Relaxed);
+                                    break;
+                                }
+                            }
+                        }
+                    };
+                    let exhausted = exhausted.clone();
+                    let mut user = user;
+                    let join_handle = tokio::spawn(async move {
+                        user.run().await;
+                        //a user only stops on its own when a feeder runs out of rows
+                        if !user.is_cancelled() {
+                            exhausted.store(true, Ordering::Relaxed);
+                        }
+                    });
+                    if let Some(last_join_handle) = shape_join_handles.insert(index, join_handle) {
+                        user_join_handles.push(last_join_handle);
+                    }
+                }
+            } else if active_users_count > target {
+                //the last spawned users are stopped first
+                let users = self.users.read();
+                for index in active_users
+                    .iter()
+                    .rev()
+                    .take((active_users_count - target) as usize)
+                {
+                    users[*index].finish();
+                }
+            }
+            select! {
+                _ = spawn_token.cancelled() => {
+                    break;
+                }
+                _ = tokio::time::sleep(Duration::from_millis(LOAD_SHAPE_TICK)) => {}
+            }
+        }
+        user_join_handles.extend(shape_join_handles.into_values());
+    }
+
+    // starts an iteration every 1 / rate seconds on an idle user of the pool, whether the previous iterations finished or not.
+    // the schedule does not drift, an iteration started late does not delay the next one
+    async fn run_at_arrival_rate(
+        &self,
+        arrival_rate: &ArrivalRate,
+        user_join_handles: &mut Vec<JoinHandle<()>>,
+    ) {
+        let (idle_sender, mut idle_receiver) = mpsc::unbounded_channel();
+        for _ in 0..arrival_rate.pre_allocated_users {
+            if let Some(user) = self.create_pool_user() {
+                let _ = idle_sender.send(user);
+            }
+        }
+        let spawn_token = self.spawn_token.lock().unwrap().clone();
+        let interval = Duration::from_secs_f64(1.0 / arrival_rate.rate);
+        let start = tokio::time::Instant::now();
+        let mut iteration: u32 = 0;
+        loop {
+            select! {
+                _ = spawn_token.cancelled() => {
+                    break;
+                }
+                _ = tokio::time::sleep_until(start + interval * iteration) => {}
+            }
+            iteration = iteration.saturating_add(1);
+            let user = match idle_receiver.try_recv() {
+                Ok(user) => Some(user),
+                Err(_) if (self.users.read().len() as u32) < arrival_rate.max_users => {
+                    self.create_pool_user()
+                }
+                Err(_) => None,
+            };
+            let Some(mut user) = user else {
+                self.results.write().add_dropped_iteration();
+                continue;
+            };
+            let idle_sender = idle_sender.clone();
+            let test_handle = self.clone();
+            user_join_handles.retain(|user_join_handle| !user_join_handle.is_finished());
+            user_join_handles.push(tokio::spawn(async move {
+                match user.run_iteration().await {
+                    Ok(_) => {
+                        let _ = idle_sender.send(user);
+                    }
+                    Err(feeder) => {
+                        //like the closed model, the test ends when a feeder runs out of rows
+                        test_handle.logger.log_buffered(
+                            LogType::Info,
+                            &format!(
+                                "Feeder [{}] ran out of rows, no more iterations are started",
+                                feeder
+                            ),
+                        );
+                        user.finish();
+                        test_handle.spawn_token.lock().unwrap().cancel();
+                    }
+                }
+            }));
+        }
+    }
+
+    fn create_pool_user(&self) -> Option<User> {
+        //the users are locked again to add the new user
+        let user_id = self.users.read().len();
+        let mut user = self.create_logged_user(user_id)?;
+        user.set_thinking(false);
+        Some(user)
+    }
+
+    fn create_logged_user(&self, user_id: usize) -> Option<User> {
+        self.logger
+            .log_buffered(LogType::Info, &format!("Creating user: [{}]", user_id));
+        match self.create_user(user_id.to_string()) {
+            Ok(user) => Some(user),
+            Err(e) => {
+                self.logger.log_buffered(
+                    LogType::Error,
+                    &format!("Error while creating user [{}]: {}", user_id, e),
+                );
+                None
+            }
+        }
+    }
+
+    // stops the users one after another, the last spawned first, so the last user stops at the end of the ramp down
+    pub async fn ramp_down(&self, ramp_down: u64) {
+        self.spawn_token.lock().unwrap().cancel();
+        let users_count = self.users.read().len();
+        if users_count == 0 {
+            return;
+        }
+        self.logger.log_buffered(
+            LogType::Info,
+            &format!("Stopping {} users in {} seconds", users_count, ramp_down),
+        );
+        let interval = Duration::from_secs(ramp_down) / users_count as u32;
+        for index in (0..users_count).rev() {
+            tokio::time::sleep(interval).await;
+            if let Some(user) = self.users.read().get(index) {
+                user.finish();
+            }
+        }
+    }
+
+    pub fn print_users(&self, active_users: u32) {
+        let mut line = format!(
+            "Status [{}] | Active users [{}/{}]",
+            self.get_status(),
+            active_users,
+            self.user_count
+        );
+        if self.arrival_rate.is_some() {
+            line.push_str(&format!(
+                " | Dropped iterations [{}]",
+                self.results.read().dropped_iterations
+            ));
+        }
+        println!("{}", line);
+    }
+
+    pub fn get_active_users_count(&self) -> u32 {
+        self.users
+            .read()
+            .iter()
+            .filter(|user| matches!(user.get_status(), Status::Running))
+            .count() as u32
+    }
+
+    // adds a snapshot of the current results to the history. does nothing if the test has not started yet
+    pub fn record_history(&self, active_users: u32) {
+        let elapsed = match self.get_elapsed_time() {
+            Some(elapsed) => elapsed.as_secs_f64(),
+            None => return,
+        };
+        let mut history = self.history.write();
+        history.record_aggregate(elapsed, &self.results.read(), active_users);
+        for (_, id, results) in self.get_all_results() {
+            history.record_endpoint(&id, elapsed, &results.read(), active_users);
+        }
+    }
+
+    pub fn get_history(&self) -> &Arc<RwLock<History>> {
+        &self.history
+    }
+
+    pub fn set_history_capacity(&self, capacity: usize) {
+        self.history.write().set_capacity(capacity);
+    }
+
     pub fn get_users(&self) -> &Arc<RwLock<Vec<User>>> {
         &self.users
     }
@@ -265,10 +1146,367 @@ impl Test {
         &self.endpoints
     }
 
+    pub fn get_thresholds(&self) -> &Arc<Vec<Threshold>> {
+        &self.thresholds
+    }
+
+    pub fn get_scenarios(&self) -> &Arc<Vec<Scenario>> {
+        &self.scenarios
+    }
+
+    pub fn get_user_classes(&self) -> &Arc<Vec<UserClass>> {
+        &self.user_classes
+    }
+
+    pub fn get_feeders(&self) -> &Arc<Vec<Feeder>> {
+        &self.feeders
+    }
+
+    pub fn get_client_config(&self) -> &Arc<ClientConfig> {
+        &self.client_config
+    }
+
+    pub fn get_cookie_config(&self) -> &Arc<Option<CookieConfig>> {
+        &self.cookie_config
+    }
+
+    pub fn get_auth(&self) -> &Arc<Option<Auth>> {
+        &self.auth
+    }
+
+    // users share the token of the test or get their own
+    pub fn create_authenticator(&self) -> Authenticator {
+        let token_cache = match &*self.auth {
+            Some(Auth::OAuth2(oauth2_config)) if oauth2_config.shared => self.token_cache.clone(),
+            _ => Arc::new(TokenCache::new()),
+        };
+        Authenticator::new(self.auth.clone(), token_cache)
+    }
+
+    // checks what can be checked before any request is sent
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        for endpoint in self.get_all_endpoints() {
+            endpoint.get_method().to_reqwest_method()?;
+            if let Some(body) = endpoint.get_body() {
+                body.validate()?;
+            }
+            for check in endpoint.get_checks() {
+                check.validate()?;
+            }
+            for extractor in endpoint.get_extractors() {
+                extractor.validate()?;
+            }
+        }
+        endpoint::validate_unique_ids(self.endpoints.iter())?;
+        if !self.user_classes.is_empty() {
+            for user_class in self.user_classes.iter() {
+                user_class.validate()?;
+            }
+            if self
+                .user_classes
+                .iter()
+                .all(|user_class| user_class.get_weight() == 0)
+            {
+                return Err("all user classes have a weight of 0".into());
+            }
+        } else if self.scenarios.is_empty() {
+            if self.endpoints.is_empty() {
+                return Err("the test has no endpoints and no scenarios".into());
+            }
+            if self.get_total_weight() == 0 {
+                return Err("all endpoints have a weight of 0".into());
+            }
+        } else if self.scenarios.iter().all(|scenario| scenario.get_weight() == 0) {
+            return Err("all scenarios have a weight of 0".into());
+        }
+        if let Some(ref load_shape) = *self.load_shape {
+            load_shape.validate()?;
+        }
+        self.get_think_time().validate()?;
+        if self.expected_interval == Some(0) {
+            return Err("the expected interval is 0".into());
+        }
+        if let Some(ref arrival_rate) = *self.arrival_rate {
+            if self.load_shape.is_some() {
+                return Err("the test has a load shape and an arrival rate".into());
+            }
+            arrival_rate.validate()?;
+        }
+        if let Some(spawn_rate) = self.spawn_rate {
+            if !(spawn_rate > 0.0 && spawn_rate.is_finite()) {
+                return Err(format!("invalid spawn rate [{}]", spawn_rate).into());
+            }
+        }
+        for threshold in self.thresholds.iter() {
+            if let Some(id) = threshold.get_endpoint() {
+                if self.find_results(id).is_none() {
+                    return Err(format!("threshold on unknown endpoint [{}]", id).into());
+                }
+            }
+        }
+        for feeder in self.feeders.iter() {
+            if feeder.get_rows().is_empty() {
+                return Err(format!("feeder [{}] has no rows", feeder.get_name()).into());
+            }
+            if *feeder.get_strategy() == FeederStrategy::Unique
+                && feeder.get_rows().len() < self.user_count as usize
+            {
+                return Err(format!(
+                    "unique feeder [{}] has {} rows for {} users",
+                    feeder.get_name(),
+                    feeder.get_rows().len(),
+                    self.user_count
+                )
+                .into());
+            }
+        }
+        self.create_clients()?;
+        Ok(())
+    }
+
+    // the endpoints of the test and the request steps of its scenarios, also of its user classes
+    pub fn get_all_endpoints(&self) -> impl Iterator<Item = &EndPoint> {
+        self.endpoints
+            .iter()
+            .chain(
+                self.scenarios
+                    .iter()
+                    .flat_map(|scenario| scenario.get_requests()),
+            )
+            .chain(
+                self.user_classes
+                    .iter()
+                    .flat_map(|user_class| user_class.get_all_endpoints()),
+            )
+    }
+
+    pub fn create_clients(&self) -> Result<Clients, Box<dyn Error>> {
+        //every user gets its own cookie jar
+        let cookie_jar = match &*self.cookie_config {
+            Some(cookie_config) => Some(Arc::new(CookieJar::new(cookie_config, &self.host)?)),
+            None => None,
+        };
+        Clients::new(&self.client_config, cookie_jar, self.get_all_endpoints())
+    }
+
+    // the kind, the id and the results of every endpoint, scenario, step and user class, in report order.
+    // endpoints are identified by method and url, scenarios by name, steps by step id and user classes by name,
+    // the ids of the endpoints, scenarios and steps of a user class start with the name of the class
+    pub fn get_all_results(&self) -> Vec<(&'static str, String, Arc<RwLock<Results>>)> {
+        let mut all_results = Vec::new();
+        for endpoint in self.endpoints.iter() {
+            all_results.push(("Endpoint", endpoint.get_id(), endpoint.get_results().clone()));
+        }
+        for scenario in self.scenarios.iter() {
+            all_results.push((
+                "Scenario",
+                scenario.get_name().clone(),
+                scenario.get_results().clone(),
+            ));
+            for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                all_results.push((
+                    "Step",
+                    scenario.get_step_id(index),
+                    endpoint.get_results().clone(),
+                ));
+            }
+        }
+        for user_class in self.user_classes.iter() {
+            for endpoint in user_class.get_endpoints().iter() {
+                all_results.push((
+                    "Endpoint",
+                    user_class.get_endpoint_id(endpoint),
+                    endpoint.get_results().clone(),
+                ));
+            }
+            for scenario in user_class.get_scenarios().iter() {
+                all_results.push((
+                    "Scenario",
+                    user_class.get_scenario_id(scenario),
+                    scenario.get_results().clone(),
+                ));
+                for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                    all_results.push((
+                        "Step",
+                        user_class.get_step_id(scenario, index),
+                        endpoint.get_results().clone(),
+                    ));
+                }
+            }
+            all_results.push((
+                "UserClass",
+                user_class.get_name().clone(),
+                user_class.get_results().clone(),
+            ));
+        }
+        all_results
+    }
+
+    pub fn find_results(&self, id: &str) -> Option<Arc<RwLock<Results>>> {
+        self.get_all_results()
+            .into_iter()
+            .find(|(_, results_id, _)| results_id == id)
+            .map(|(_, _, results)| results)
+    }
+
+    // thresholds on unknown targets are rejected by validate
+    pub fn evaluate_threshold(&self, threshold: &Threshold) -> Option<ThresholdBreach> {
+        match threshold.get_endpoint() {
+            Some(id) => self
+                .find_results(id)
+                .and_then(|results| threshold.evaluate(&results.read())),
+            None => threshold.evaluate(&self.results.read()),
+        }
+    }
+
+    pub fn evaluate_thresholds(&self) -> Vec<ThresholdBreach> {
+        self.thresholds
+            .iter()
+            .filter_map(|threshold| self.evaluate_threshold(threshold))
+            .collect()
+    }
+
+    // thresholds are only checked once there are requests to judge
+    pub fn should_abort(&self) -> bool {
+        if self.results.read().total_requests == 0 {
+            return false;
+        }
+        for threshold in self.thresholds.iter().filter(|t| t.get_abort_on_fail()) {
+            if let Some(breach) = self.evaluate_threshold(threshold) {
+                self.logger.log_buffered(
+                    LogType::Warning,
+                    &format!("Threshold breached, aborting: {}", breach),
+                );
+                return true;
+            }
+        }
+        false
+    }
+
+    // the breached thresholds, none if the test passed
+    pub fn get_verdict(&self) -> Option<String> {
+        let breaches = self.evaluate_thresholds();
+        if breaches.is_empty() {
+            return None;
+        }
+        let message = breaches
+            .iter()
+            .map(|breach| breach.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        Some(message)
+    }
+
+    // a stopped test or a test with an error keeps its status
+    fn set_verdict(&self) {
+        if !matches!(self.get_status(), Status::Finished) {
+            return;
+        }
+        if let Some(message) = self.get_verdict() {
+            self.logger.log_buffered(
+                LogType::Warning,
+                &format!("Test failed, thresholds breached: {}", message),
+            );
+            self.set_status(Status::Failed(message));
+        }
+    }
+
+    pub fn get_host(&self) -> &String {
+        &self.host
+    }
+
+    pub fn get_html_report_path(&self) -> Option<&String> {
+        self.html_report_path.as_ref()
+    }
+
+    async fn write_html_report(&self) {
+        if let Some(path) = self.get_html_report_path() {
+            match html::write_html(self, None, path).await {
+                Ok(_) => {
+                    self.logger.log_buffered(
+                        LogType::Info,
+                        &format!("HTML report written to [{}]", path),
+                    );
+                }
+                Err(e) => {
+                    self.logger.log_buffered(
+                        LogType::Error,
+                        &format!("Error while writing HTML report: {}", e),
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn get_csv_report_path(&self) -> Option<&String> {
+        self.csv_report_path.as_ref()
+    }
+
+    pub fn get_history_report_path(&self) -> Option<&String> {
+        self.history_report_path.as_ref()
+    }
+
+    pub fn get_junit_report_path(&self) -> Option<&String> {
+        self.junit_report_path.as_ref()
+    }
+
+    // the csv, history and junit reports. the master writes them from its combined results with its own logger
+    pub async fn write_reports(&self, logger: &Logger) {
+        if let Some(path) = self.get_csv_report_path() {
+            log_report(logger, "CSV", path, report::write_csv(self, path).await);
+        }
+        if let Some(path) = self.get_history_report_path() {
+            log_report(logger, "History", path, report::write_history_jsonl(self, path).await);
+        }
+        if let Some(path) = self.get_junit_report_path() {
+            log_report(logger, "JUnit", path, report::write_junit_xml(self, path).await);
+        }
+    }
+
+    pub fn get_current_window(&self) -> Duration {
+        Duration::from_secs(self.current_window)
+    }
+
     pub fn get_run_time(&self) -> &Option<u64> {
         &self.run_time
     }
 
+    pub fn get_think_time(&self) -> ThinkTime {
+        match self.think_time {
+            Some(ref think_time) => think_time.clone(),
+            None => ThinkTime::from_sleep(self.sleep),
+        }
+    }
+
+    pub fn get_spawn_rate(&self) -> &Option<f64> {
+        &self.spawn_rate
+    }
+
+    pub fn get_ramp_down(&self) -> &Option<u64> {
+        &self.ramp_down
+    }
+
+    pub fn get_load_shape(&self) -> &Arc<Option<LoadShape>> {
+        &self.load_shape
+    }
+
+    pub fn get_arrival_rate(&self) -> &Arc<Option<ArrivalRate>> {
+        &self.arrival_rate
+    }
+
+    pub fn get_expected_interval(&self) -> &Option<u64> {
+        &self.expected_interval
+    }
+
+    // a test with a load shape finishes when the shape ends, or after the run time if it is shorter
+    pub fn get_effective_run_time(&self) -> Option<u64> {
+        match (self.run_time, &*self.load_shape) {
+            (Some(run_time), Some(load_shape)) => Some(run_time.min(load_shape.get_duration())),
+            (None, Some(load_shape)) => Some(load_shape.get_duration()),
+            (run_time, None) => run_time,
+        }
+    }
+
     pub fn get_start_timestamp(&self) -> &Arc<RwLock<Option<Instant>>> {
         &self.start_timestamp
     }
@@ -277,21 +1515,110 @@ impl Test {
         &self.end_timestamp
     }
 
+    pub fn create_scenarios_sent_results(&self) -> HashMap<String, SentResults> {
+        let mut scenarios_sent_results = HashMap::new();
+        for scenario in self.scenarios.iter() {
+            scenarios_sent_results.insert(
+                scenario.get_name().clone(),
+                scenario.get_results().read().create_sent_results(),
+            );
+        }
+        for user_class in self.user_classes.iter() {
+            for scenario in user_class.get_scenarios().iter() {
+                scenarios_sent_results.insert(
+                    user_class.get_scenario_id(scenario),
+                    scenario.get_results().read().create_sent_results(),
+                );
+            }
+        }
+        scenarios_sent_results
+    }
+
+    pub fn create_steps_sent_results(&self) -> HashMap<String, SentResults> {
+        let mut steps_sent_results = HashMap::new();
+        for scenario in self.scenarios.iter() {
+            for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                steps_sent_results.insert(
+                    scenario.get_step_id(index),
+                    endpoint.get_results().read().create_sent_results(),
+                );
+            }
+        }
+        for user_class in self.user_classes.iter() {
+            for scenario in user_class.get_scenarios().iter() {
+                for (index, endpoint) in scenario.get_requests().into_iter().enumerate() {
+                    steps_sent_results.insert(
+                        user_class.get_step_id(scenario, index),
+                        endpoint.get_results().read().create_sent_results(),
+                    );
+                }
+            }
+        }
+        steps_sent_results
+    }
+
     pub fn create_endpoints_sent_results(&self) -> HashMap<String, SentResults> {
         let mut endpoints_sent_results = HashMap::new();
         for endpoint in self.endpoints.iter() {
             endpoints_sent_results.insert(
-                endpoint.get_url().clone(),
-                endpoint.get_results().read().create_sent_results()
+                endpoint.get_id(),
+                endpoint.get_results().read().create_sent_results(),
             );
         }
+        for user_class in self.user_classes.iter() {
+            for endpoint in user_class.get_endpoints().iter() {
+                endpoints_sent_results.insert(
+                    user_class.get_endpoint_id(endpoint),
+                    endpoint.get_results().read().create_sent_results(),
+                );
+            }
+        }
         endpoints_sent_results
     }
+
+    pub fn create_user_classes_sent_results(&self) -> HashMap<String, SentResults> {
+        let mut user_classes_sent_results = HashMap::new();
+        for user_class in self.user_classes.iter() {
+            user_classes_sent_results.insert(
+                user_class.get_name().clone(),
+                user_class.get_results().read().create_sent_results(),
+            );
+        }
+        user_classes_sent_results
+    }
+}
+
+fn log_report(logger: &Logger, kind: &str, path: &str, result: Result<(), Box<dyn Error>>) {
+    match result {
+        Ok(_) => {
+            logger.log_buffered(
+                LogType::Info,
+                &format!("{} report written to [{}]", kind, path),
+            );
+        }
+        Err(e) => {
+            logger.log_buffered(
+                LogType::Error,
+                &format!("Error while writing {} report: {}", kind, e),
+            );
+        }
+    }
 }
 
 #[async_trait]
 impl Runnable for Test {
     async fn run(&mut self) {
+        //an invalid test would fail every user
+        if let Some(message) = self
+            .validate()
+            .err()
+            .map(|e| format!("Invalid test: {}", e))
+        {
+            self.logger.log_buffered(LogType::Error, &message);
+            self.set_status(Status::Error(message));
+            let _ = self.logger.flush_buffer().await;
+            return;
+        }
         self.set_start_timestamp(Instant::now());
         self.set_status(Status::Running);
         //run background thread
@@ -301,31 +1628,59 @@ impl Runnable for Test {
         });
         //set run time
         let mut run_message = String::from("Test running forever, press ctrl+c to stop");
-        if let Some(run_time) = self.run_time {
+        if let Some(run_time) = self.get_effective_run_time() {
             run_message = format!("Test running for {} seconds", run_time);
             let test_handle = self.clone();
             tokio::spawn(async move {
                 tokio::time::sleep(Duration::from_secs(run_time)).await;
+                if let Some(ramp_down) = test_handle.ramp_down {
+                    test_handle.ramp_down(ramp_down).await;
+                }
                 test_handle.finish();
                 test_handle
                     .logger
-                    .log_buffered(LogType::Info, &format!("Test finished"));
+                    .log_buffered(LogType::Info, "Test finished");
             });
         }
         self.logger.log_buffered(LogType::Info, &run_message);
         let mut user_join_handles = vec![];
-        for i in 0..self.user_count {
-            let user_id = i;
-            self.logger
-                .log_buffered(LogType::Info, &format!("Spawning user: [{}]", user_id));
-            let mut user = self.create_user(user_id.to_string());
-            let user_join_handle = tokio::spawn(async move {
-                user.run().await;
-            });
-            user_join_handles.push(user_join_handle);
+        match (&*self.load_shape.clone(), &*self.arrival_rate.clone()) {
+            (_, Some(arrival_rate)) => {
+                self.logger.log_buffered(
+                    LogType::Info,
+                    &format!("Starting iterations at: {}", arrival_rate),
+                );
+                self.run_at_arrival_rate(arrival_rate, &mut user_join_handles)
+                    .await;
+            }
+            (Some(load_shape), None) => {
+                self.logger.log_buffered(
+                    LogType::Info,
+                    &format!("Following load shape: {}", load_shape),
+                );
+                self.follow_load_shape(load_shape, &mut user_join_handles)
+                    .await;
+            }
+            (None, None) => {
+                let spawn_token = self.spawn_token.lock().unwrap().clone();
+                for i in 0..self.user_count {
+                    //users are spawned one after another, the first one right away
+                    if let (Some(spawn_rate), true) = (self.spawn_rate, i > 0) {
+                        select! {
+                            _ = spawn_token.cancelled() => {
+                                break;
+                            }
+                            _ = tokio::time::sleep(Duration::from_secs_f64(1.0 / spawn_rate)) => {}
+                        }
+                    }
+                    if let Some(user_join_handle) = self.spawn_user(i) {
+                        user_join_handles.push(user_join_handle);
+                    }
+                }
+            }
         }
         self.logger
-            .log_buffered(LogType::Info, &format!("All users have been spawned"));
+            .log_buffered(LogType::Info, "All users have been spawned");
         for join_handle in user_join_handles {
             match join_handle.await {
                 Ok(_) => {}
@@ -336,8 +1691,10 @@ impl Runnable for Test {
             }
         }
         self.set_end_timestamp(Instant::now());
+        self.calculate_percentiles();
+        self.set_verdict();
         self.logger
-            .log_buffered(LogType::Info, &format!("All users have been stopped"));
+            .log_buffered(LogType::Info, "All users have been stopped");
         //stop background thread
         self.background_token.lock().unwrap().cancel();
         match background_join_handle.await {
@@ -347,13 +1704,16 @@ impl Runnable for Test {
             }
         }
         self.logger
-            .log_buffered(LogType::Info, &format!("Background thread stopped"));
+            .log_buffered(LogType::Info, "Background thread stopped");
+        self.write_html_report().await;
+        self.write_reports(&self.logger).await;
         //flush buffer
         let _ = self.logger.flush_buffer().await;
     }
 
     fn stop(&self) {
         self.set_status(Status::Stopped);
+        self.spawn_token.lock().unwrap().cancel();
         for user in self.users.read().iter() {
             user.stop();
         }
@@ -361,6 +1721,7 @@ impl Runnable for Test {
 
     fn finish(&self) {
         self.set_status(Status::Finished);
+        self.spawn_token.lock().unwrap().cancel();
         for user in self.users.read().iter() {
             user.finish();
         }
@@ -391,12 +1752,11 @@ impl fmt::Display for Test {
 
         write!(
             f,
-            "Status [{}] | Users [{}] | RunTime [{}] | Sleep [{} - {}] | Host [{}] | GlobalHeaders [{:?}] | Results [{}] | StartTimestamp [{:?}] | EndTimestamp [{:?}] | ElapsedTime [{:?}]",
+            "Status [{}] | Users [{}] | RunTime [{}] | ThinkTime [{}] | Host [{}] | GlobalHeaders [{:?}] | Results [{}] | StartTimestamp [{:?}] | EndTimestamp [{:?}] | ElapsedTime [{:?}]",
             self.status.read(),
             self.user_count,
             self.run_time.unwrap_or(0),
-            self.sleep.0,
-            self.sleep.1,
+            self.get_think_time(),
             self.host,
             global_headers,
             self.results.read(),
@@ -408,16 +1768,20 @@ impl fmt::Display for Test {
 }
 
 impl HasResults for Test {
-    fn add_response_time(&self, response_time: u32) {
+    fn add_response_time(&self, response_time: u64) {
         self.results.write().add_response_time(response_time);
     }
 
-    fn add_failed(&self) {
-        self.results.write().add_failed();
+    fn add_failed(&self, status_code: u16) {
+        self.results.write().add_failed(status_code);
+    }
+
+    fn add_failed_check(&self, name: &str, reason: &str) {
+        self.results.write().add_failed_check(name, reason);
     }
 
-    fn add_connection_error(&self) {
-        self.results.write().add_connection_error();
+    fn add_connection_error(&self, kind: ErrorKind, message: &str) {
+        self.results.write().add_connection_error(kind, message);
     }
 
     fn set_requests_per_second(&self, requests_per_second: f64) {
@@ -434,6 +1798,12 @@ impl HasResults for Test {
         for endpoint in self.endpoints.iter() {
             endpoint.calculate_requests_per_second(elapsed);
         }
+        for scenario in self.scenarios.iter() {
+            scenario.calculate_requests_per_second(elapsed);
+        }
+        for user_class in self.user_classes.iter() {
+            user_class.calculate_requests_per_second(elapsed);
+        }
     }
 
     fn calculate_failed_requests_per_second(&self, elapsed: &Duration) {
@@ -446,6 +1816,46 @@ impl HasResults for Test {
         for endpoint in self.endpoints.iter() {
             endpoint.calculate_failed_requests_per_second(elapsed);
         }
+        for scenario in self.scenarios.iter() {
+            scenario.calculate_failed_requests_per_second(elapsed);
+        }
+        for user_class in self.user_classes.iter() {
+            user_class.calculate_failed_requests_per_second(elapsed);
+        }
+    }
+
+    fn calculate_current_requests_per_second(&self, elapsed: &Duration, window: &Duration) {
+        self.results
+            .write()
+            .calculate_current_requests_per_second(elapsed, window);
+        for user in self.users.read().iter() {
+            user.calculate_current_requests_per_second(elapsed, window);
+        }
+        for endpoint in self.endpoints.iter() {
+            endpoint.calculate_current_requests_per_second(elapsed, window);
+        }
+        for scenario in self.scenarios.iter() {
+            scenario.calculate_current_requests_per_second(elapsed, window);
+        }
+        for user_class in self.user_classes.iter() {
+            user_class.calculate_current_requests_per_second(elapsed, window);
+        }
+    }
+
+    fn calculate_percentiles(&self) {
+        self.results.write().calculate_percentiles();
+        for user in self.users.read().iter() {
+            user.calculate_percentiles();
+        }
+        for endpoint in self.endpoints.iter() {
+            endpoint.calculate_percentiles();
+        }
+        for scenario in self.scenarios.iter() {
+            scenario.calculate_percentiles();
+        }
+        for user_class in self.user_classes.iter() {
+            user_class.calculate_percentiles();
+        }
     }
 
     fn get_results(&self) -> Arc<RwLock<Results>> {
@@ -462,16 +1872,31 @@ impl Serialize for Test {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Test", 12)?;
+        let mut state = serializer.serialize_struct("Test", 27)?;
         state.serialize_field("id", &self.id)?;
         state.serialize_field("status", &*self.status.read())?;
         state.serialize_field("user_count", &self.user_count)?;
         state.serialize_field("run_time", &self.run_time)?;
+        state.serialize_field("spawn_rate", &self.spawn_rate)?;
+        state.serialize_field("ramp_down", &self.ramp_down)?;
+        state.serialize_field("load_shape", &*self.load_shape)?;
+        state.serialize_field("arrival_rate", &*self.arrival_rate)?;
+        state.serialize_field("expected_interval", &self.expected_interval)?;
         state.serialize_field("sleep", &self.sleep)?;
+        state.serialize_field("think_time", &self.think_time)?;
         state.serialize_field("host", &*self.host)?;
         state.serialize_field("endpoints", &*self.endpoints)?;
         state.serialize_field("global_headers", &*self.global_headers)?;
         state.serialize_field("results", &*self.results.read())?;
+        state.serialize_field("history", &*self.history.read())?;
+        state.serialize_field("current_window", &self.current_window)?;
+        state.serialize_field("thresholds", &*self.thresholds)?;
+        state.serialize_field("client_config", &*self.client_config)?;
+        state.serialize_field("cookie_config", &*self.cookie_config)?;
+        state.serialize_field("auth", &*self.auth)?;
+        state.serialize_field("scenarios", &*self.scenarios)?;
+        state.serialize_field("user_classes", &*self.user_classes)?;
+        state.serialize_field("feeders", &*self.feeders)?;
         state.serialize_field("users", &*self.users.read())?;
         state.serialize_field("logger", &*self.logger)?;
         state.serialize_field("print_stats_to_console", &*self.print_stats_to_console)?;
@@ -493,11 +1918,26 @@ impl<'de> Deserialize<'de> for Test {
             Status,
             UserCount,
             RunTime,
+            SpawnRate,
+            RampDown,
+            LoadShape,
+            ArrivalRate,
+            ExpectedInterval,
             Sleep,
+            ThinkTime,
             Host,
             Endpoints,
             GlobalHeaders,
             Results,
+            History,
+            CurrentWindow,
+            Thresholds,
+            ClientConfig,
+            CookieConfig,
+            Auth,
+            Scenarios,
+            UserClasses,
+            Feeders,
             Users,
             Logger,
             PrintStatsToConsole,
@@ -517,11 +1957,26 @@ impl<'de> Deserialize<'de> for Test {
                 let mut status: Option<Status> = None;
                 let mut user_count: Option<u32> = None;
                 let mut run_time: Option<Option<u64>> = None;
+                let mut spawn_rate: Option<Option<f64>> = None;
+                let mut ramp_down: Option<Option<u64>> = None;
+                let mut load_shape: Option<Option<LoadShape>> = None;
+                let mut arrival_rate: Option<Option<ArrivalRate>> = None;
+                let mut expected_interval: Option<Option<u64>> = None;
                 let mut sleep: Option<(u64, u64)> = None;
+                let mut think_time: Option<Option<ThinkTime>> = None;
                 let mut host: Option<String> = None;
                 let mut endpoints: Option<Vec<EndPoint>> = None;
                 let mut global_headers: Option<Option<HashMap<String, String>>> = None;
                 let mut results: Option<Results> = None;
+                let mut history: Option<History> = None;
+                let mut current_window: Option<u64> = None;
+                let mut thresholds: Option<Vec<Threshold>> = None;
+                let mut client_config: Option<ClientConfig> = None;
+                let mut cookie_config: Option<Option<CookieConfig>> = None;
+                let mut auth: Option<Option<Auth>> = None;
+                let mut scenarios: Option<Vec<Scenario>> = None;
+                let mut user_classes: Option<Vec<UserClass>> = None;
+                let mut feeders: Option<Vec<Feeder>> = None;
                 let mut users: Option<Vec<User>> = None;
                 let mut logger: Option<Logger> = None;
                 let mut print_stats_to_console: Option<bool> = None;
@@ -552,12 +2007,48 @@ impl<'de> Deserialize<'de> for Test {
                             }
                             run_time = Some(map.next_value()?);
                         }
+                        Field::SpawnRate => {
+                            if spawn_rate.is_some() {
+                                return Err(serde::de::Error::duplicate_field("spawn_rate"));
+                            }
+                            spawn_rate = Some(map.next_value()?);
+                        }
+                        Field::RampDown => {
+                            if ramp_down.is_some() {
+                                return Err(serde::de::Error::duplicate_field("ramp_down"));
+                            }
+                            ramp_down = Some(map.next_value()?);
+                        }
+                        Field::LoadShape => {
+                            if load_shape.is_some() {
+                                return Err(serde::de::Error::duplicate_field("load_shape"));
+                            }
+                            load_shape = Some(map.next_value()?);
+                        }
+                        Field::ArrivalRate => {
+                            if arrival_rate.is_some() {
+                                return Err(serde::de::Error::duplicate_field("arrival_rate"));
+                            }
+                            arrival_rate = Some(map.next_value()?);
+                        }
+                        Field::ExpectedInterval => {
+                            if expected_interval.is_some() {
+                                return Err(serde::de::Error::duplicate_field("expected_interval"));
+                            }
+                            expected_interval = Some(map.next_value()?);
+                        }
                         Field::Sleep => {
                             if sleep.is_some() {
                                 return Err(serde::de::Error::duplicate_field("sleep"));
                             }
                             sleep = Some(map.next_value()?);
                         }
+                        Field::ThinkTime => {
+                            if think_time.is_some() {
+                                return Err(serde::de::Error::duplicate_field("think_time"));
+                            }
+                            think_time = Some(map.next_value()?);
+                        }
                         Field::Host => {
                             if host.is_some() {
                                 return Err(serde::de::Error::duplicate_field("host"));
@@ -582,6 +2073,60 @@ impl<'de> Deserialize<'de> for Test {
                             }
                             results = Some(map.next_value()?);
                         }
+                        Field::History => {
+                            if history.is_some() {
+                                return Err(serde::de::Error::duplicate_field("history"));
+                            }
+                            history = Some(map.next_value()?);
+                        }
+                        Field::CurrentWindow => {
+                            if current_window.is_some() {
+                                return Err(serde::de::Error::duplicate_field("current_window"));
+                            }
+                            current_window = Some(map.next_value()?);
+                        }
+                        Field::Thresholds => {
+                            if thresholds.is_some() {
+                                return Err(serde::de::Error::duplicate_field("thresholds"));
+                            }
+                            thresholds = Some(map.next_value()?);
+                        }
+                        Field::ClientConfig => {
+                            if client_config.is_some() {
+                                return Err(serde::de::Error::duplicate_field("client_config"));
+                            }
+                            client_config = Some(map.next_value()?);
+                        }
+                        Field::CookieConfig => {
+                            if cookie_config.is_some() {
+                                return Err(serde::de::Error::duplicate_field("cookie_config"));
+                            }
+                            cookie_config = Some(map.next_value()?);
+                        }
+                        Field::Auth => {
+                            if auth.is_some() {
+                                return Err(serde::de::Error::duplicate_field("auth"));
+                            }
+                            auth = Some(map.next_value()?);
+                        }
+                        Field::Scenarios => {
+                            if scenarios.is_some() {
+                                return Err(serde::de::Error::duplicate_field("scenarios"));
+                            }
+                            scenarios = Some(map.next_value()?);
+                        }
+                        Field::UserClasses => {
+                            if user_classes.is_some() {
+                                return Err(serde::de::Error::duplicate_field("user_classes"));
+                            }
+                            user_classes = Some(map.next_value()?);
+                        }
+                        Field::Feeders => {
+                            if feeders.is_some() {
+                                return Err(serde::de::Error::duplicate_field("feeders"));
+                            }
+                            feeders = Some(map.next_value()?);
+                        }
                         Field::Users => {
                             if users.is_some() {
                                 return Err(serde::de::Error::duplicate_field("users"));
@@ -611,13 +2156,29 @@ impl<'de> Deserialize<'de> for Test {
                     user_count.ok_or_else(|| serde::de::Error::missing_field("user_count"))?;
                 let run_time =
                     run_time.ok_or_else(|| serde::de::Error::missing_field("run_time"))?;
+                let spawn_rate = spawn_rate.unwrap_or_default();
+                let ramp_down = ramp_down.unwrap_or_default();
+                let load_shape = load_shape.unwrap_or_default();
+                let arrival_rate = arrival_rate.unwrap_or_default();
+                let expected_interval = expected_interval.unwrap_or_default();
                 let sleep = sleep.ok_or_else(|| serde::de::Error::missing_field("sleep"))?;
+                let think_time = think_time.unwrap_or_default();
                 let host = host.ok_or_else(|| serde::de::Error::missing_field("host"))?;
                 let endpoints =
                     endpoints.ok_or_else(|| serde::de::Error::missing_field("endpoints"))?;
                 let global_headers = global_headers
                     .ok_or_else(|| serde::de::Error::missing_field("global_headers"))?;
                 let results = results.ok_or_else(|| serde::de::Error::missing_field("results"))?;
+                // optional, older tests have none of these
+                let history = history.unwrap_or_default();
+                let current_window = current_window.unwrap_or(DEFAULT_CURRENT_WINDOW);
+                let thresholds = thresholds.unwrap_or_default();
+                let client_config = client_config.unwrap_or_default();
+                let cookie_config = cookie_config.unwrap_or_default();
+                let auth = auth.unwrap_or_default();
+                let scenarios = scenarios.unwrap_or_default();
+                let user_classes = user_classes.unwrap_or_default();
+                let feeders = feeders.unwrap_or_default();
                 let users = users.ok_or_else(|| serde::de::Error::missing_field("users"))?;
                 let logger = logger.ok_or_else(|| serde::de::Error::missing_field("logger"))?;
                 let print_stats_to_console = print_stats_to_console
@@ -629,33 +2190,71 @@ impl<'de> Deserialize<'de> for Test {
                     background_token: Arc::new(Mutex::new(CancellationToken::new())),
                     user_count,
                     run_time,
+                    spawn_rate,
+                    ramp_down,
+                    spawn_token: Arc::new(Mutex::new(CancellationToken::new())),
+                    load_shape: Arc::new(load_shape),
+                    arrival_rate: Arc::new(arrival_rate),
+                    expected_interval,
                     sleep,
+                    think_time,
                     host: Arc::new(host),
                     endpoints: Arc::new(endpoints),
                     global_headers: Arc::new(global_headers),
                     results: Arc::new(RwLock::new(results)),
+                    history: Arc::new(RwLock::new(history)),
+                    current_window,
+                    thresholds: Arc::new(thresholds),
+                    client_config: Arc::new(client_config),
+                    cookie_config: Arc::new(cookie_config),
+                    auth: Arc::new(auth),
+                    token_cache: Arc::new(TokenCache::new()),
+                    scenarios: Arc::new(scenarios),
+                    user_classes: Arc::new(user_classes),
+                    class_users: Arc::new(RwLock::new(Vec::new())),
+                    feeders: Arc::new(feeders),
+                    global_counter: Arc::new(AtomicU64::new(0)),
                     start_timestamp: Arc::new(RwLock::new(None)),
                     end_timestamp: Arc::new(RwLock::new(None)),
                     users: Arc::new(RwLock::new(users)),
                     logger: Arc::new(logger),
                     print_stats_to_console: Arc::new(print_stats_to_console),
+                    html_report_path: None,
+                    csv_report_path: None,
+                    history_report_path: None,
+                    junit_report_path: None,
                 })
             }
         }
-        const FIELDS: &'static [&'static str] = &[
+        const FIELDS: &[&str] = &[
             "id",
             "status",
             "user_count",
             "run_time",
+            "spawn_rate",
+            "ramp_down",
+            "load_shape",
+            "arrival_rate",
+            "expected_interval",
             "sleep",
+            "think_time",
             "host",
             "endpoints",
             "global_headers",
             "results",
+            "history",
+            "current_window",
+            "thresholds",
+            "client_config",
+            "cookie_config",
+            "auth",
+            "scenarios",
+            "user_classes",
+            "feeders",
             "users",
             "logger",
         ];
-        deserializer.deserialize_struct("Test", &FIELDS, TestVisitor)
+        deserializer.deserialize_struct("Test", FIELDS, TestVisitor)
     }
 }
 
@@ -732,3 +2331,168 @@ impl TestInfo {
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::threshold::{Comparison, Metric};
+
+    fn create_test(user_count: u32) -> Test {
+        Test::new(
+            String::from("test"),
+            user_count,
+            None,
+            (0, 0),
+            String::from("http://127.0.0.1"),
+            vec![EndPoint::new_get(String::from("/"), None, None)],
+            None,
+            String::new(),
+            false,
+            false,
+        )
+    }
+
+    fn finished_users(test: &Test) -> Vec<bool> {
+        test.users
+            .read()
+            .iter()
+            .map(|user| matches!(user.get_status(), Status::Finished))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn ramp_down_stops_the_last_spawned_users_first() {
+        tokio::time::pause();
+        let test = create_test(4);
+        for user_id in 0..4 {
+            test.create_user(user_id.to_string()).unwrap();
+        }
+        let test_handle = test.clone();
+        let ramp_down_join_handle = tokio::spawn(async move {
+            test_handle.ramp_down(4).await;
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(finished_users(&test), vec![false, false, false, false]);
+        assert!(test.spawn_token.lock().unwrap().is_cancelled());
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(finished_users(&test), vec![false, false, false, true]);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(finished_users(&test), vec![false, false, true, true]);
+        ramp_down_join_handle.await.unwrap();
+        assert_eq!(finished_users(&test), vec![true, true, true, true]);
+    }
+
+    #[test]
+    fn breached_thresholds_abort_and_fail_the_test() {
+        let mut test = create_test(1);
+        let mut failure_rate = Threshold::new(Metric::FailureRate, Comparison::LessThan, 0.1);
+        failure_rate.set_abort_on_fail(true);
+        let mut max_response_time =
+            Threshold::new(Metric::MaxResponseTime, Comparison::LessThan, 100.0);
+        max_response_time.set_endpoint(Some(String::from("GET /")));
+        test.set_thresholds(vec![failure_rate, max_response_time]);
+        assert!(!test.should_abort());
+        assert!(test.get_verdict().is_none());
+
+        for _ in 0..10 {
+            test.add_response_time(50_000);
+        }
+        test.get_endpoints()[0].add_response_time(200_000);
+        // only the thresholds with abort on fail abort the test
+        assert!(!test.should_abort());
+        let verdict = test.get_verdict().unwrap();
+        assert!(verdict.contains("[GET /]"), "{}", verdict);
+
+        for _ in 0..5 {
+            test.add_failed(500);
+        }
+        assert!(test.should_abort());
+        assert_eq!(test.evaluate_thresholds().len(), 2);
+
+        test.set_status(Status::Stopped);
+        test.set_verdict();
+        assert!(matches!(test.get_status(), Status::Stopped));
+        test.set_status(Status::Finished);
+        test.set_verdict();
+        assert!(matches!(test.get_status(), Status::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn iterations_are_dropped_when_all_users_are_busy() {
+        tokio::time::pause();
+        // the listener never accepts, so every request hangs and keeps its user busy
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut test = Test::new(
+            String::from("test"),
+            0,
+            None,
+            (0, 0),
+            format!("http://{}", listener.local_addr().unwrap()),
+            vec![EndPoint::new_get(String::from("/"), None, None)],
+            None,
+            String::new(),
+            false,
+            false,
+        );
+        let arrival_rate = ArrivalRate::new(10.0, 1, 3);
+        test.set_arrival_rate(Some(arrival_rate.clone()));
+        let test_handle = test.clone();
+        let join_handle = tokio::spawn(async move {
+            test_handle
+                .run_at_arrival_rate(&arrival_rate, &mut Vec::new())
+                .await;
+        });
+        // iterations are started at 0, 100, ..., 900 milliseconds
+        tokio::time::sleep(Duration::from_millis(950)).await;
+        test.spawn_token.lock().unwrap().cancel();
+        join_handle.await.unwrap();
+        assert_eq!(test.users.read().len(), 3);
+        assert_eq!(test.results.read().dropped_iterations, 7);
+        let mut results = Results::new();
+        results.combine_sent_results(&test.results.read().create_sent_results());
+        assert_eq!(results.dropped_iterations, 7);
+    }
+
+    fn weighted_user_class(name: &str, weight: u32) -> UserClass {
+        let mut user_class = UserClass::new(
+            name.to_string(),
+            vec![EndPoint::new_get(String::from("/"), None, None)],
+            vec![],
+        );
+        user_class.set_weight(weight);
+        user_class
+    }
+
+    #[test]
+    fn user_classes_are_selected_by_their_deficit() {
+        let mut test = create_test(8);
+        assert!(test.select_user_class().is_none());
+        test.set_user_classes(vec![
+            weighted_user_class("a", 3),
+            weighted_user_class("b", 1),
+            weighted_user_class("c", 0),
+        ]);
+        let names: Vec<String> = (0..8)
+            .map(|_| test.select_user_class().unwrap().get_name().clone())
+            .collect();
+        assert_eq!(names, vec!["a", "a", "b", "a", "a", "a", "b", "a"]);
+    }
+
+    #[test]
+    fn partitioned_user_classes_add_up_to_the_weights() {
+        let mut test = create_test(8);
+        test.set_user_classes(vec![weighted_user_class("a", 3), weighted_user_class("b", 1)]);
+        let weights = |test: &Test| -> Vec<u32> {
+            test.user_classes
+                .iter()
+                .map(|user_class| user_class.get_weight())
+                .collect()
+        };
+        let mut first_worker = test.clone();
+        first_worker.partition_user_classes(0, 5);
+        let mut second_worker = test.clone();
+        second_worker.partition_user_classes(5, 3);
+        assert_eq!(weights(&first_worker), vec![3, 2]);
+        assert_eq!(weights(&second_worker), vec![3, 0]);
+    }
+}
@@ -1,8 +1,20 @@
-use crate::{EndPoint, HasResults, LogType, Logger, Method, Results, Runnable, Status};
+use crate::{
+    auth::Authenticator,
+    check::CheckResponse,
+    client::Clients,
+    errors::ErrorKind,
+    feeder::{Feeder, FeederStrategy, Row},
+    scenario::Step,
+    template::{self, Generators},
+    think_time::ThinkTime,
+    ClientConfig, EndPoint, HasResults, Histogram, LogType, Logger, Results, Runnable, Scenario,
+    Status,
+};
 use async_trait::async_trait;
+use futures::future::BoxFuture;
 use parking_lot::RwLock;
-use rand::Rng;
-use reqwest::{Client, RequestBuilder};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use reqwest::RequestBuilder;
 use serde::{
     de::{MapAccess, Visitor},
     ser::SerializeStruct,
@@ -17,26 +29,56 @@ use std::{
 use tokio::select;
 use tokio_util::sync::CancellationToken;
 
+/// Presets for the think time of the users.
 #[derive(Clone, Debug)]
 pub enum UserBehaviour {
-    //TODO
-    AGGRESSIVE,
-    PASSIVE,
-    LAZY,
+    AGGRESSIVE, //NO THINKING
+    PASSIVE,    //1 - 3 SECONDS
+    LAZY,       //10 SECONDS ON AVERAGE, EXPONENTIALLY DISTRIBUTED
+}
+
+impl UserBehaviour {
+    pub fn get_think_time(&self) -> ThinkTime {
+        match self {
+            UserBehaviour::AGGRESSIVE => ThinkTime::Constant(0),
+            UserBehaviour::PASSIVE => ThinkTime::Uniform {
+                min: 1000,
+                max: 3000,
+            },
+            UserBehaviour::LAZY => ThinkTime::Exponential { mean: 10000 },
+        }
+    }
+}
+
+impl From<UserBehaviour> for ThinkTime {
+    fn from(user_behaviour: UserBehaviour) -> ThinkTime {
+        user_behaviour.get_think_time()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct User {
-    client: Client,
+    clients: Clients,
+    generators: Generators,
+    authenticator: Authenticator,
     token: Arc<Mutex<CancellationToken>>,
     status: Arc<RwLock<Status>>,
     id: String,
-    sleep: (u64, u64),
+    think_time: ThinkTime,
+    thinking: bool, //FALSE FOR USERS RUNNING ITERATIONS AT AN ARRIVAL RATE
+    expected_interval: Option<u64>, //MICROSECONDS, ENABLES THE COORDINATED OMISSION CORRECTION
     host: Arc<String>,
     global_endpoints: Arc<Vec<EndPoint>>,
+    endpoint_distribution: Option<WeightedIndex<u32>>, //NONE IF NO ENDPOINT HAS A WEIGHT
+    global_scenarios: Arc<Vec<Scenario>>,
+    scenario_distribution: Option<WeightedIndex<u32>>,
+    global_feeders: Arc<Vec<Feeder>>,
+    unique_row: Row, //THE ROWS OF THE UNIQUE FEEDERS, TAKEN ONCE BEFORE THE USER RUNS
+    variables: HashMap<String, String>, //EXTRACTED FROM RESPONSES, CLEARED BEFORE EVERY SCENARIO RUN
     global_headers: Arc<Option<HashMap<String, String>>>,
     global_results: Arc<RwLock<Results>>, //GLOBAL RESULTS OF A TEST (ALL USERS)
     results: Arc<RwLock<Results>>, //USER RESULTS
+    class_results: Option<Arc<RwLock<Results>>>, //RESULTS OF THE USER CLASS (ALL USERS OF THE CLASS)
     endpoints: Arc<RwLock<HashMap<String, Results>>>,
     logger: Arc<Logger>,
 }
@@ -54,23 +96,40 @@ impl fmt::Display for User {
 }
 
 impl User {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: String,
-        sleep: (u64, u64),
+        clients: Clients,
+        generators: Generators,
+        authenticator: Authenticator,
+        think_time: ThinkTime,
         host: Arc<String>,
         global_endpoints: Arc<Vec<EndPoint>>,
+        global_scenarios: Arc<Vec<Scenario>>,
+        global_feeders: Arc<Vec<Feeder>>,
         global_headers: Arc<Option<HashMap<String, String>>>,
         global_results: Arc<RwLock<Results>>,
         logger: Arc<Logger>,
     ) -> User {
         User {
-            client: Client::new(),
+            clients,
+            generators,
+            authenticator,
             token: Arc::new(Mutex::new(CancellationToken::new())),
             status: Arc::new(RwLock::new(Status::Created)),
             id,
-            sleep,
+            think_time,
+            thinking: true,
+            expected_interval: None,
+            class_results: None,
             host,
+            endpoint_distribution: User::create_endpoint_distribution(&global_endpoints),
             global_endpoints,
+            scenario_distribution: User::create_scenario_distribution(&global_scenarios),
+            global_scenarios,
+            unique_row: Row::new(),
+            global_feeders,
+            variables: HashMap::new(),
             global_headers,
             global_results,
             results: Arc::new(RwLock::new(Results::new())),
@@ -82,150 +141,479 @@ impl User {
     fn add_headers(&self, mut request: RequestBuilder, endpoint: &EndPoint) -> RequestBuilder {
         if let Some(global_headers) = &*self.global_headers {
             for (key, value) in global_headers {
-                request = request.header(key, value);
+                request = request.header(
+                    key,
+                    template::render(value, &self.variables, &self.generators),
+                );
             }
         }
         if let Some(headers) = &endpoint.headers {
             for (key, value) in headers {
-                request = request.header(key, value);
+                request = request.header(
+                    key,
+                    template::render(value, &self.variables, &self.generators),
+                );
             }
         }
         request
     }
 
+    // takes the rows of all unique feeders, merged. every row is used by exactly one user,
+    // so a user without a row must not run. returns the name of the feeder that ran out of rows
+    pub fn take_unique_row(&mut self) -> Result<(), String> {
+        let mut unique_row = Row::new();
+        for feeder in self
+            .global_feeders
+            .iter()
+            .filter(|feeder| *feeder.get_strategy() == FeederStrategy::Unique)
+        {
+            match feeder.next_row() {
+                Some(row) => unique_row.extend(row.clone()),
+                None => return Err(feeder.get_name().clone()),
+            }
+        }
+        self.unique_row = unique_row;
+        Ok(())
+    }
+
+    // puts the next rows of the feeders into the variables. returns the name of the feeder that ran out of rows
+    fn feed(&mut self) -> Result<(), String> {
+        let feeders = self.global_feeders.clone();
+        for feeder in feeders.iter() {
+            if *feeder.get_strategy() == FeederStrategy::Unique {
+                continue;
+            }
+            match feeder.next_row() {
+                Some(row) => self.variables.extend(row.clone()),
+                None => return Err(feeder.get_name().clone()),
+            }
+        }
+        self.variables.extend(self.unique_row.clone());
+        Ok(())
+    }
+
+    pub fn set_thinking(&mut self, thinking: bool) {
+        self.thinking = thinking;
+    }
+
+    pub fn set_expected_interval(&mut self, expected_interval: Option<u64>) {
+        self.expected_interval = expected_interval;
+    }
+
+    pub fn set_class_results(&mut self, class_results: Option<Arc<RwLock<Results>>>) {
+        self.class_results = class_results;
+    }
+
+    // a finished user gets a new token to run again. its results and unique row are kept
+    pub fn reset(&self) {
+        *self.token.lock().unwrap() = CancellationToken::new();
+        self.set_status(Status::Created);
+    }
+
+    // false if the user stopped on its own, which only happens when a feeder runs out of rows
+    pub fn is_cancelled(&self) -> bool {
+        self.token.lock().unwrap().is_cancelled()
+    }
+
     async fn run_forever(&mut self) {
         self.set_status(Status::Running);
         loop {
-            tokio::time::sleep(Duration::from_secs(self.select_random_sleep())).await;
-            let endpoint = self.select_random_endpoint();
-            let url = format!("{}{}", self.host, endpoint.get_url());
-            let mut request = match endpoint.get_method() {
-                /*
-                 Method::GET(params) => {
-                    let mut request = self.client.get(&url);
-                    if let Some(params) = params {
-                        request = request.query(params);
-                    }
-                    request
+            let start = Instant::now();
+            if let Err(feeder) = self.iterate().await {
+                self.logger.log_buffered(
+                    LogType::Info,
+                    &format!("User: [{}] | Feeder [{}] ran out of rows", self.id, feeder),
+                );
+                self.set_status(Status::Finished);
+                break;
+            }
+            if let Some(remainder) = self.think_time.get_pacing_remainder(start.elapsed()) {
+                tokio::time::sleep(remainder).await;
+            }
+        }
+    }
 
-                },
-                Method::POST(body) => {
-                    let mut request = self.client.post(&url);
-                    if let Some(body) = body {
-                        request = request.body(body.to_owned());
-                    }
-                    request
+    // runs a single iteration, used by the arrival rate executor. returns the name of the feeder that ran out of rows
+    pub async fn run_iteration(&mut self) -> Result<(), String> {
+        let token = self.token.lock().unwrap().clone();
+        if token.is_cancelled() {
+            return Ok(());
+        }
+        if let Status::Created = self.get_status() {
+            self.set_status(Status::Running);
+        }
+        select! {
+            _ = token.cancelled() => {
+                Ok(())
+            }
+            result = self.iterate() => {
+                result
+            }
+        }
+    }
 
+    // one request, or one scenario run if the test has scenarios. returns the name of the feeder that ran out of rows
+    async fn iterate(&mut self) -> Result<(), String> {
+        if !self.global_scenarios.is_empty() {
+            //every scenario run starts without the variables of the previous run
+            self.variables.clear();
+            if let Some(cookie_jar) = self.clients.get_cookie_jar() {
+                if cookie_jar.resets_per_scenario() {
+                    cookie_jar.reset();
                 }
-                */
-                Method::GET => {
-                    let mut request = self.client.get(&url);
-                    if let Some(ref params) = endpoint.params {
-                        request = request.query(params);
+            }
+        }
+        self.feed()?;
+        if self.global_scenarios.is_empty() {
+            let endpoints = self.global_endpoints.clone();
+            let endpoint = &endpoints[self.select_random_endpoint_index()];
+            self.think().await;
+            //the results of the request are recorded, there is nothing left to do with a failure
+            let _ = self.execute_endpoint(endpoint).await;
+        } else {
+            self.run_scenario().await;
+        }
+        Ok(())
+    }
+
+    async fn think(&self) {
+        if !self.thinking {
+            return;
+        }
+        tokio::time::sleep(self.think_time.sample()).await;
+    }
+
+    // runs a scenario from start to end
+    async fn run_scenario(&mut self) {
+        let scenarios = self.global_scenarios.clone();
+        let scenario = &scenarios[self.select_random_scenario_index()];
+        let start = Instant::now();
+        match self.run_steps(scenario.get_steps()).await {
+            Ok(_) => {
+                scenario.add_response_time(start.elapsed().as_micros() as u64);
+            }
+            Err((step, reason)) => {
+                self.logger.log_buffered(
+                    LogType::Error,
+                    &format!(
+                        "User: [{}] | Scenario [{}] failed at step [{}]: {}",
+                        self.id,
+                        scenario.get_name(),
+                        step,
+                        reason
+                    ),
+                );
+                scenario.add_failed_step(&step, &reason);
+            }
+        }
+    }
+
+    // the first failed step stops the run, the error holds the step and the reason
+    fn run_steps<'a>(&'a mut self, steps: &'a [Step]) -> BoxFuture<'a, Result<(), (String, String)>> {
+        Box::pin(async move {
+            for step in steps.iter() {
+                match step {
+                    Step::Request(endpoint) => {
+                        self.think().await;
+                        self.execute_endpoint(endpoint).await.map_err(|reason| {
+                            (endpoint.get_id(), reason)
+                        })?;
                     }
-                    request
-                }
-                Method::POST => {
-                    let mut request = self.client.post(&url);
-                    if let Some(ref body) = endpoint.body {
-                        request = request.body(body.to_owned());
+                    Step::Loop { times, steps } => {
+                        for _ in 0..*times {
+                            self.run_steps(steps).await?;
+                        }
                     }
-                    request
-                }
-                Method::PUT => {
-                    let mut request = self.client.put(&url);
-                    if let Some(ref body) = endpoint.body {
-                        request = request.body(body.to_owned());
+                    Step::Branch {
+                        condition,
+                        steps,
+                        else_steps,
+                    } => {
+                        if condition.evaluate(&self.variables) {
+                            self.run_steps(steps).await?;
+                        } else {
+                            self.run_steps(else_steps).await?;
+                        }
+                    }
+                    Step::Set { name, value } => {
+                        let value = template::render(value, &self.variables, &self.generators);
+                        self.variables.insert(name.clone(), value);
                     }
-                    request
                 }
-                Method::DELETE => self.client.delete(&url),
+            }
+            Ok(())
+        })
+    }
+
+    // sends the request and records the results. returns the reason if the request failed
+    async fn execute_endpoint(&mut self, endpoint: &EndPoint) -> Result<(), String> {
+        let client = self.clients.get_client(endpoint);
+        let url = format!(
+            "{}{}",
+            self.host,
+            template::render(endpoint.get_url(), &self.variables, &self.generators)
+        );
+        let method = match endpoint.get_method().to_reqwest_method() {
+            Ok(method) => method,
+            Err(e) => {
+                self.add_endpoint_connection_error(ErrorKind::Other, &e.to_string(), endpoint);
+                return Err(e.to_string());
+            }
+        };
+        let mut request = client.request(method, &url);
+        if let Some(ref params) = endpoint.params {
+            let params: Vec<(&String, String)> = params
+                .iter()
+                .map(|(key, value)| {
+                    (
+                        key,
+                        template::render(value, &self.variables, &self.generators),
+                    )
+                })
+                .collect();
+            request = request.query(&params);
+        }
+        if let Some(ref body) = endpoint.body {
+            let render = |text: &str| template::render(text, &self.variables, &self.generators);
+            request = match body.apply(request, render) {
+                Ok(request) => request,
+                Err(e) => {
+                    let message = e.to_string();
+                    self.add_endpoint_connection_error(ErrorKind::Other, &message, endpoint);
+                    return Err(message);
+                }
             };
-            request = self.add_headers(request, endpoint);
-            let start = Instant::now();
-            if let Ok(response) = request.send().await {
-                let duration = start.elapsed();
+        }
+        request = self.add_headers(request, endpoint);
+        request = match self
+            .authenticator
+            .authorize(request, self.clients.get_default_client(), &self.host)
+            .await
+        {
+            Ok(request) => request,
+            Err(reason) => {
                 self.logger.log_buffered(
-                    LogType::Info,
-                    &format!(
-                        "User: [{}] | {} {} | {:?}",
-                        self.id,
-                        response.status(),
-                        url,
-                        duration
-                    ),
+                    LogType::Error,
+                    &format!("User: [{}] | {} | {}", self.id, ErrorKind::Auth, reason),
                 );
-                let status_code = response.status().as_u16();
-                if status_code >= 200 && status_code < 400 {
-                    self.add_endpoint_response_time(duration.as_millis() as u32, endpoint);
-                } else {
-                    //failed request. It has no response time
-                    self.add_endpoint_failed(endpoint);
-                }
-            } else {
+                //the request was not sent. Counted like a connection error
+                self.add_endpoint_connection_error(ErrorKind::Auth, &reason, endpoint);
+                return Err(format!("{}: {}", ErrorKind::Auth, reason));
+            }
+        };
+        let start = Instant::now();
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(error) => {
+                let kind = ErrorKind::from_reqwest_error(&error);
                 self.logger.log_buffered(
                     LogType::Error,
                     &format!(
                         "User: [{}] | {} {} | {:?}",
                         self.id,
-                        "ConnectionError",
+                        kind,
                         url,
                         start.elapsed()
                     ),
                 );
                 //connection error. This will not increase the failed counter or the request counter. It has also no response time
-                self.add_endpoint_connection_error(endpoint);
+                self.add_endpoint_connection_error(kind, &error.to_string(), endpoint);
+                return Err(kind.to_string());
+            }
+        };
+        let duration = start.elapsed();
+        self.logger.log_buffered(
+            LogType::Info,
+            &format!(
+                "User: [{}] | {} {} | {:?}",
+                self.id,
+                response.status(),
+                url,
+                duration
+            ),
+        );
+        let status_code = response.status().as_u16();
+        if !endpoint.has_status_check() && !(200..400).contains(&status_code) {
+            //failed request. It has no response time
+            self.add_endpoint_failed(status_code, endpoint);
+            return Err(format!("status code {}", status_code));
+        }
+        if endpoint.get_checks().is_empty() && endpoint.get_extractors().is_empty() {
+            self.add_endpoint_response_time(duration.as_micros() as u64, endpoint);
+            return Ok(());
+        }
+        let headers = response.headers().clone();
+        let body = if endpoint.needs_body() {
+            match response.text().await {
+                Ok(body) => Some(body),
+                Err(error) => {
+                    //the body could not be read. Counted as a connection error
+                    self.add_endpoint_connection_error(
+                        ErrorKind::BodyRead,
+                        &error.to_string(),
+                        endpoint,
+                    );
+                    return Err(ErrorKind::BodyRead.to_string());
+                }
+            }
+        } else {
+            None
+        };
+        let check_response = CheckResponse {
+            status_code,
+            headers: &headers,
+            body: body.as_deref(),
+            response_time: duration,
+        };
+        let failed_check = endpoint.get_checks().iter().find_map(|check| {
+            check
+                .evaluate(&check_response)
+                .err()
+                .map(|reason| (check, reason))
+        });
+        if let Some((check, reason)) = failed_check {
+            self.logger.log_buffered(
+                LogType::Error,
+                &format!(
+                    "User: [{}] | Check [{}] failed: {} | {}",
+                    self.id, check, reason, url
+                ),
+            );
+            //failed check. Like a failed request it has no response time
+            self.add_endpoint_failed_check(check.get_name(), &reason, endpoint);
+            return Err(format!("check [{}] failed: {}", check, reason));
+        }
+        let mut extracted = Vec::with_capacity(endpoint.get_extractors().len());
+        for extractor in endpoint.get_extractors().iter() {
+            match extractor.extract(&check_response) {
+                Ok(value) => extracted.push((extractor.get_variable().clone(), value)),
+                Err(reason) => {
+                    self.logger.log_buffered(
+                        LogType::Error,
+                        &format!(
+                            "User: [{}] | [{}] failed: {} | {}",
+                            self.id, extractor, reason, url
+                        ),
+                    );
+                    //failed extraction. Counted like a failed check
+                    self.add_endpoint_failed_check(&extractor.to_string(), &reason, endpoint);
+                    return Err(format!("[{}] failed: {}", extractor, reason));
+                }
             }
         }
+        self.variables.extend(extracted);
+        self.add_endpoint_response_time(duration.as_micros() as u64, endpoint);
+        Ok(())
     }
 
     fn set_status(&self, status: Status) {
         *self.status.write() = status;
     }
 
-    fn select_random_endpoint(&self) -> &EndPoint {
+    fn create_endpoint_distribution(endpoints: &[EndPoint]) -> Option<WeightedIndex<u32>> {
+        WeightedIndex::new(endpoints.iter().map(|endpoint| endpoint.get_weight())).ok()
+    }
+
+    // endpoints are selected proportionally to their weights, uniformly if no endpoint has a weight
+    fn select_random_endpoint_index(&self) -> usize {
         let mut rng = rand::thread_rng();
-        let index = rng.gen_range(0..self.global_endpoints.len());
-        &self.global_endpoints[index]
+        match self.endpoint_distribution {
+            Some(ref endpoint_distribution) => endpoint_distribution.sample(&mut rng),
+            None => rng.gen_range(0..self.global_endpoints.len()),
+        }
     }
 
-    fn select_random_sleep(&self) -> u64 {
+    fn create_scenario_distribution(scenarios: &[Scenario]) -> Option<WeightedIndex<u32>> {
+        WeightedIndex::new(scenarios.iter().map(|scenario| scenario.get_weight())).ok()
+    }
+
+    fn select_random_scenario_index(&self) -> usize {
         let mut rng = rand::thread_rng();
-        rng.gen_range(self.sleep.0..self.sleep.1)
+        match self.scenario_distribution {
+            Some(ref scenario_distribution) => scenario_distribution.sample(&mut rng),
+            None => rng.gen_range(0..self.global_scenarios.len()),
+        }
+    }
+
+    pub fn get_variables(&self) -> &HashMap<String, String> {
+        &self.variables
     }
 
     pub fn get_endpoints(&self) -> &Arc<RwLock<HashMap<String, Results>>> {
         &self.endpoints
     }
 
-    fn add_endpoint_failed(&self, endpoint: &EndPoint) {
-        endpoint.add_failed();
+    fn add_endpoint_failed(&self, status_code: u16, endpoint: &EndPoint) {
+        endpoint.add_failed(status_code);
+        self.endpoints
+            .write()
+            .entry(endpoint.get_id())
+            .or_default()
+            .add_failed(status_code);
+        self.add_failed(status_code);
+    }
+
+    fn add_endpoint_failed_check(&self, name: &str, reason: &str, endpoint: &EndPoint) {
+        endpoint.add_failed_check(name, reason);
         self.endpoints
             .write()
-            .entry(endpoint.url.clone())
-            .or_insert(Results::new())
-            .add_failed();
-        self.add_failed();
+            .entry(endpoint.get_id())
+            .or_default()
+            .add_failed_check(name, reason);
+        self.add_failed_check(name, reason);
     }
 
-    fn add_endpoint_connection_error(&self, endpoint: &EndPoint) {
-        endpoint.add_connection_error();
+    fn add_endpoint_connection_error(&self, kind: ErrorKind, message: &str, endpoint: &EndPoint) {
+        endpoint.add_connection_error(kind, message);
         self.endpoints
             .write()
-            .entry(endpoint.url.clone())
-            .or_insert(Results::new())
-            .add_connection_error();
-        self.add_connection_error();
+            .entry(endpoint.get_id())
+            .or_default()
+            .add_connection_error(kind, message);
+        self.add_connection_error(kind, message);
     }
 
-    fn add_endpoint_response_time(&self, response_time: u32, endpoint: &EndPoint) {
+    fn add_endpoint_response_time(&self, response_time: u64, endpoint: &EndPoint) {
         endpoint.add_response_time(response_time);
         self.endpoints
             .write()
-            .entry(endpoint.url.clone())
-            .or_insert(Results::new())
+            .entry(endpoint.get_id())
+            .or_default()
             .add_response_time(response_time);
         self.add_response_time(response_time);
+        if let Some(expected_interval) = self.expected_interval {
+            self.add_endpoint_corrected_response_time(response_time, expected_interval, endpoint);
+        }
+    }
+
+    // expected_interval is the time between two requests of the user, in microseconds
+    fn add_endpoint_corrected_response_time(
+        &self,
+        response_time: u64,
+        expected_interval: u64,
+        endpoint: &EndPoint,
+    ) {
+        let corrected_histogram = Histogram::corrected(response_time, expected_interval);
+        endpoint
+            .get_results()
+            .write()
+            .add_corrected_histogram(&corrected_histogram);
+        self.endpoints
+            .write()
+            .entry(endpoint.get_id())
+            .or_default()
+            .add_corrected_histogram(&corrected_histogram);
+        self.global_results
+            .write()
+            .add_corrected_histogram(&corrected_histogram);
+        self.results
+            .write()
+            .add_corrected_histogram(&corrected_histogram);
+        if let Some(ref class_results) = self.class_results {
+            class_results
+                .write()
+                .add_corrected_histogram(&corrected_histogram);
+        }
     }
 }
 
@@ -278,19 +666,38 @@ impl Drop for User {
 }
 
 impl HasResults for User {
-    fn add_response_time(&self, response_time: u32) {
+    fn add_response_time(&self, response_time: u64) {
         self.global_results.write().add_response_time(response_time);
         self.results.write().add_response_time(response_time);
+        if let Some(ref class_results) = self.class_results {
+            class_results.write().add_response_time(response_time);
+        }
     }
 
-    fn add_failed(&self) {
-        self.global_results.write().add_failed();
-        self.results.write().add_failed();
+    fn add_failed(&self, status_code: u16) {
+        self.global_results.write().add_failed(status_code);
+        self.results.write().add_failed(status_code);
+        if let Some(ref class_results) = self.class_results {
+            class_results.write().add_failed(status_code);
+        }
     }
 
-    fn add_connection_error(&self) {
-        self.global_results.write().add_connection_error();
-        self.results.write().add_connection_error();
+    fn add_failed_check(&self, name: &str, reason: &str) {
+        self.global_results.write().add_failed_check(name, reason);
+        self.results.write().add_failed_check(name, reason);
+        if let Some(ref class_results) = self.class_results {
+            class_results.write().add_failed_check(name, reason);
+        }
+    }
+
+    fn add_connection_error(&self, kind: ErrorKind, message: &str) {
+        self.global_results
+            .write()
+            .add_connection_error(kind, message);
+        self.results.write().add_connection_error(kind, message);
+        if let Some(ref class_results) = self.class_results {
+            class_results.write().add_connection_error(kind, message);
+        }
     }
 
     fn set_requests_per_second(&self, requests_per_second: f64) {
@@ -315,6 +722,22 @@ impl HasResults for User {
         }
     }
 
+    fn calculate_current_requests_per_second(&self, elapsed: &Duration, window: &Duration) {
+        self.results
+            .write()
+            .calculate_current_requests_per_second(elapsed, window);
+        for (_, endpoint_result) in self.endpoints.write().iter_mut() {
+            endpoint_result.calculate_current_requests_per_second(elapsed, window);
+        }
+    }
+
+    fn calculate_percentiles(&self) {
+        self.results.write().calculate_percentiles();
+        for (_, endpoint_result) in self.endpoints.write().iter_mut() {
+            endpoint_result.calculate_percentiles();
+        }
+    }
+
     fn get_results(&self) -> Arc<RwLock<Results>> {
         self.results.clone()
     }
@@ -333,7 +756,7 @@ impl Serialize for User {
         let mut state = serializer.serialize_struct("User", 10)?;
         state.serialize_field("status", &*self.status.read())?;
         state.serialize_field("id", &self.id)?;
-        state.serialize_field("sleep", &self.sleep)?;
+        state.serialize_field("think_time", &self.think_time)?;
         state.serialize_field("host", &*self.host)?;
         state.serialize_field("global_endpoints", &*self.global_endpoints)?;
         state.serialize_field("global_headers", &*self.global_headers)?;
@@ -357,7 +780,7 @@ impl<'de> Deserialize<'de> for User {
         enum Field {
             Status,
             Id,
-            Sleep,
+            ThinkTime,
             Host,
             GlobalEndpoints,
             GlobalHeaders,
@@ -379,7 +802,7 @@ impl<'de> Deserialize<'de> for User {
             {
                 let mut status: Option<Status> = None;
                 let mut id: Option<String> = None;
-                let mut sleep: Option<(u64, u64)> = None;
+                let mut think_time: Option<ThinkTime> = None;
                 let mut host: Option<String> = None;
                 let mut global_endpoints: Option<Vec<EndPoint>> = None;
                 let mut global_headers: Option<Option<HashMap<String, String>>> = None;
@@ -402,11 +825,11 @@ impl<'de> Deserialize<'de> for User {
                             }
                             id = Some(map.next_value()?);
                         }
-                        Field::Sleep => {
-                            if sleep.is_some() {
-                                return Err(serde::de::Error::duplicate_field("sleep"));
+                        Field::ThinkTime => {
+                            if think_time.is_some() {
+                                return Err(serde::de::Error::duplicate_field("think_time"));
                             }
-                            sleep = Some(map.next_value()?);
+                            think_time = Some(map.next_value()?);
                         }
                         Field::Host => {
                             if host.is_some() {
@@ -455,7 +878,8 @@ impl<'de> Deserialize<'de> for User {
 
                 let status = status.ok_or_else(|| serde::de::Error::missing_field("status"))?;
                 let id = id.ok_or_else(|| serde::de::Error::missing_field("id"))?;
-                let sleep = sleep.ok_or_else(|| serde::de::Error::missing_field("sleep"))?;
+                let think_time =
+                    think_time.ok_or_else(|| serde::de::Error::missing_field("think_time"))?;
                 let host = host.ok_or_else(|| serde::de::Error::missing_field("host"))?;
                 let global_endpoints = global_endpoints
                     .ok_or_else(|| serde::de::Error::missing_field("global_endpoints"))?;
@@ -468,14 +892,29 @@ impl<'de> Deserialize<'de> for User {
                     endpoints.ok_or_else(|| serde::de::Error::missing_field("endpoints"))?;
                 let logger = logger.ok_or_else(|| serde::de::Error::missing_field("logger"))?;
 
+                //clients, scenarios, feeders and variables are not serialized, a deserialized user gets default clients
+                let clients = Clients::new(&ClientConfig::default(), None, global_endpoints.iter())
+                    .map_err(serde::de::Error::custom)?;
+
                 Ok(User {
-                    client: Client::new(),
+                    clients,
+                    generators: Generators::default(),
+                    authenticator: Authenticator::default(),
                     token: Arc::new(Mutex::new(CancellationToken::new())),
                     status: Arc::new(RwLock::new(status)),
                     id,
-                    sleep,
+                    think_time,
+                    thinking: true,
+                    expected_interval: None,
+                    class_results: None,
                     host: Arc::new(host),
+                    endpoint_distribution: User::create_endpoint_distribution(&global_endpoints),
                     global_endpoints: Arc::new(global_endpoints),
+                    global_scenarios: Arc::new(Vec::new()),
+                    scenario_distribution: None,
+                    global_feeders: Arc::new(Vec::new()),
+                    unique_row: Row::new(),
+                    variables: HashMap::new(),
                     global_headers: Arc::new(global_headers),
                     global_results: Arc::new(RwLock::new(global_results)),
                     results: Arc::new(RwLock::new(results)),
@@ -484,10 +923,10 @@ impl<'de> Deserialize<'de> for User {
                 })
             }
         }
-        const FIELDS: &'static [&'static str] = &[
+        const FIELDS: &[&str] = &[
             "status",
             "id",
-            "sleep",
+            "think_time",
             "host",
             "global_endpoints",
             "global_headers",
@@ -497,6 +936,190 @@ impl<'de> Deserialize<'de> for User {
             "logger",
         ];
 
-        deserializer.deserialize_struct("User", &FIELDS, UserVisitor)
+        deserializer.deserialize_struct("User", FIELDS, UserVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cookies::CookieConfig, scenario::Condition, Test};
+    use reqwest::{cookie::CookieStore, header::HeaderValue};
+    use url::Url;
+
+    fn create_test(endpoints: Vec<EndPoint>) -> Test {
+        create_test_on(String::from("http://127.0.0.1"), endpoints)
+    }
+
+    fn create_test_on(host: String, endpoints: Vec<EndPoint>) -> Test {
+        Test::new(
+            String::from("test"),
+            1,
+            None,
+            (0, 0),
+            host,
+            endpoints,
+            None,
+            String::new(),
+            false,
+            false,
+        )
+    }
+
+    fn weighted_endpoint(url: &str, weight: u32) -> EndPoint {
+        let mut endpoint = EndPoint::new_get(url.to_string(), None, None);
+        endpoint.set_weight(weight);
+        endpoint
+    }
+
+    // how often each index is selected out of 4000 selections
+    fn count_selections(select: impl Fn() -> usize, len: usize) -> Vec<u32> {
+        let mut counts = vec![0; len];
+        for _ in 0..4000 {
+            counts[select()] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn endpoints_are_selected_by_their_weights() {
+        let test = create_test(vec![
+            weighted_endpoint("/a", 3),
+            weighted_endpoint("/b", 1),
+            weighted_endpoint("/c", 0),
+        ]);
+        let user = test.create_user(String::from("0")).unwrap();
+        let counts = count_selections(|| user.select_random_endpoint_index(), 3);
+        assert_eq!(counts[2], 0);
+        assert!((2800..3200).contains(&counts[0]), "{:?}", counts);
+    }
+
+    #[test]
+    fn endpoints_without_weights_are_selected_uniformly() {
+        let test = create_test(vec![weighted_endpoint("/a", 0), weighted_endpoint("/b", 0)]);
+        let user = test.create_user(String::from("0")).unwrap();
+        assert!(user.endpoint_distribution.is_none());
+        let counts = count_selections(|| user.select_random_endpoint_index(), 2);
+        assert!((1800..2200).contains(&counts[0]), "{:?}", counts);
+    }
+
+    #[test]
+    fn scenarios_are_selected_by_their_weights() {
+        let mut test = create_test(vec![]);
+        let mut checkout = Scenario::new(String::from("checkout"), vec![]);
+        checkout.set_weight(1);
+        let mut browse = Scenario::new(String::from("browse"), vec![]);
+        browse.set_weight(4);
+        test.set_scenarios(vec![checkout, browse]);
+        let user = test.create_user(String::from("0")).unwrap();
+        let counts = count_selections(|| user.select_random_scenario_index(), 2);
+        assert!((600..1000).contains(&counts[0]), "{:?}", counts);
+    }
+
+    #[test]
+    fn the_actual_mix_counts_connection_errors() {
+        let test = create_test(vec![weighted_endpoint("/a", 3), weighted_endpoint("/b", 1)]);
+        let endpoints = test.get_endpoints();
+        for _ in 0..2 {
+            endpoints[0].add_response_time(1000);
+        }
+        endpoints[1].add_failed(500);
+        endpoints[1].add_connection_error(ErrorKind::Timeout, "timed out");
+        assert_eq!(test.get_expected_mix(&endpoints[0]), 0.75);
+        assert_eq!(test.get_actual_mix(&endpoints[0]), 0.5);
+        assert_eq!(test.get_actual_mix(&endpoints[1]), 0.5);
+    }
+
+    fn set(name: &str, value: &str) -> Step {
+        Step::Set {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn scenario_steps_loop_and_branch() {
+        let test = create_test(vec![]);
+        let mut user = test.create_user(String::from("0")).unwrap();
+        let steps = vec![
+            set("trail", ""),
+            Step::Loop {
+                times: 3,
+                steps: vec![set("trail", "{{trail}}x")],
+            },
+            Step::Branch {
+                condition: Condition::Equals {
+                    name: String::from("trail"),
+                    value: String::from("xxx"),
+                },
+                steps: vec![set("looped", "yes")],
+                else_steps: vec![set("looped", "no")],
+            },
+            Step::Branch {
+                condition: Condition::Exists(String::from("missing")),
+                steps: vec![set("missing", "found")],
+                else_steps: vec![],
+            },
+        ];
+        user.run_steps(&steps).await.unwrap();
+        assert_eq!(user.get_variables()["trail"], "xxx");
+        assert_eq!(user.get_variables()["looped"], "yes");
+        assert!(!user.get_variables().contains_key("missing"));
+    }
+
+    #[tokio::test]
+    async fn a_failed_request_step_stops_the_run() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let test = create_test_on(format!("http://{}", address), vec![]);
+        let mut user = test.create_user(String::from("0")).unwrap();
+        let steps = vec![
+            Step::Loop {
+                times: 2,
+                steps: vec![Step::Request(Box::new(EndPoint::new_get(
+                    String::from("/"),
+                    None,
+                    None,
+                )))],
+            },
+            set("after", "yes"),
+        ];
+        let (step, _) = user.run_steps(&steps).await.unwrap_err();
+        assert_eq!(step, "GET /");
+        assert!(!user.get_variables().contains_key("after"));
+        assert_eq!(user.results.read().total_connection_errors, 1);
+    }
+
+    // sets a cookie in the jar of the user, runs a scenario and returns the cookies left
+    async fn cookies_after_a_scenario_run(reset_per_scenario: bool) -> (String, Option<String>) {
+        let mut test = create_test(vec![]);
+        test.set_scenarios(vec![Scenario::new(String::from("scenario"), vec![set("a", "b")])]);
+        let mut cookie_config = CookieConfig::new();
+        cookie_config.set_reset_per_scenario(reset_per_scenario);
+        test.set_cookie_config(Some(cookie_config));
+        let mut user = test.create_user(String::from("0")).unwrap();
+        let other_user = test.create_user(String::from("1")).unwrap();
+        let cookie_jar = user.clients.get_cookie_jar().clone().unwrap();
+        let url = Url::parse("http://127.0.0.1").unwrap();
+        let set_cookie = HeaderValue::from_static("cart=3");
+        cookie_jar.set_cookies(&mut std::iter::once(&set_cookie), &url);
+        let other_cookie_jar = other_user.clients.get_cookie_jar().clone().unwrap();
+        assert!(other_cookie_jar.cookies(&url).is_none());
+        let cookies_before = cookie_jar.cookies(&url).unwrap().to_str().unwrap().to_string();
+        user.iterate().await.unwrap();
+        let cookies_after = cookie_jar
+            .cookies(&url)
+            .map(|cookies| cookies.to_str().unwrap().to_string());
+        (cookies_before, cookies_after)
+    }
+
+    #[tokio::test]
+    async fn cookie_jars_reset_per_scenario_run_if_configured() {
+        let (before, after) = cookies_after_a_scenario_run(true).await;
+        assert_eq!(before, "cart=3");
+        assert_eq!(after, None);
+        let (before, after) = cookies_after_a_scenario_run(false).await;
+        assert_eq!(after, Some(before));
     }
 }
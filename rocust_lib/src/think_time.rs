@@ -0,0 +1,154 @@
+use rand::Rng;
+use rand_distr::{Distribution, Exp, Normal};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, time::Duration};
+
+/// The time a user waits before every request, all values in milliseconds.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ThinkTime {
+    Constant(u64),
+    Uniform { min: u64, max: u64 },     //BOTH INCLUDED
+    Normal { mean: u64, std_dev: u64 }, //NEGATIVE VALUES ARE CUT TO 0
+    Exponential { mean: u64 },          //REQUESTS ARRIVE LIKE A POISSON PROCESS
+    Pacing(u64), //EVERY ITERATION TAKES THIS LONG, THE USER WAITS FOR THE REST AFTER THE ITERATION
+}
+
+impl ThinkTime {
+    // the think time of tests created with a sleep range in seconds
+    pub fn from_sleep(sleep: (u64, u64)) -> ThinkTime {
+        ThinkTime::Uniform {
+            min: sleep.0 * 1000,
+            max: sleep.1 * 1000,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if let ThinkTime::Uniform { min, max } = self {
+            if min > max {
+                return Err(
+                    format!("invalid think time, [{}] ms is above [{}] ms", min, max).into(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // the time to wait before a request. zero for pacing, the wait comes after the iteration
+    pub fn sample(&self) -> Duration {
+        let mut rng = rand::thread_rng();
+        let millis = match *self {
+            ThinkTime::Constant(millis) => millis as f64,
+            ThinkTime::Uniform { min, max } if min >= max => min as f64,
+            ThinkTime::Uniform { min, max } => rng.gen_range(min..=max) as f64,
+            ThinkTime::Normal { mean, std_dev } => Normal::new(mean as f64, std_dev as f64)
+                .map(|normal| normal.sample(&mut rng))
+                .unwrap_or(mean as f64),
+            ThinkTime::Exponential { mean: 0 } => 0.0,
+            ThinkTime::Exponential { mean } => Exp::new(1.0 / mean as f64)
+                .map(|exp| exp.sample(&mut rng))
+                .unwrap_or(mean as f64),
+            ThinkTime::Pacing(_) => 0.0,
+        };
+        Duration::from_secs_f64(millis.max(0.0) / 1000.0)
+    }
+
+    // the time left to wait after an iteration that took elapsed, none if the think time is not pacing
+    pub fn get_pacing_remainder(&self, elapsed: Duration) -> Option<Duration> {
+        match *self {
+            ThinkTime::Pacing(millis) => {
+                Some(Duration::from_millis(millis).saturating_sub(elapsed))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ThinkTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThinkTime::Constant(millis) => write!(f, "CONSTANT {} ms", millis),
+            ThinkTime::Uniform { min, max } => write!(f, "UNIFORM {} - {} ms", min, max),
+            ThinkTime::Normal { mean, std_dev } => {
+                write!(f, "NORMAL {} ms +- {} ms", mean, std_dev)
+            }
+            ThinkTime::Exponential { mean } => write!(f, "EXPONENTIAL {} ms", mean),
+            ThinkTime::Pacing(millis) => write!(f, "PACING {} ms", millis),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_millis(think_time: &ThinkTime, samples: usize) -> Vec<u128> {
+        (0..samples)
+            .map(|_| think_time.sample().as_millis())
+            .collect()
+    }
+
+    #[test]
+    fn samples_stay_within_their_bounds() {
+        assert_eq!(
+            ThinkTime::Constant(250).sample(),
+            Duration::from_millis(250)
+        );
+        let uniform = sample_millis(&ThinkTime::Uniform { min: 10, max: 20 }, 1000);
+        assert!(uniform.iter().all(|millis| (10..=20).contains(millis)));
+        assert!(uniform.contains(&10) && uniform.contains(&20));
+        assert_eq!(
+            ThinkTime::Uniform { min: 30, max: 30 }.sample(),
+            Duration::from_millis(30)
+        );
+        assert_eq!(
+            ThinkTime::from_sleep((1, 3)),
+            ThinkTime::Uniform {
+                min: 1000,
+                max: 3000
+            }
+        );
+        assert_eq!(ThinkTime::Exponential { mean: 0 }.sample(), Duration::ZERO);
+        assert_eq!(ThinkTime::Pacing(1000).sample(), Duration::ZERO);
+    }
+
+    #[test]
+    fn normal_samples_are_never_negative() {
+        let normal = ThinkTime::Normal {
+            mean: 10,
+            std_dev: 100,
+        };
+        let millis = sample_millis(&normal, 1000);
+        // about half of the values are below 1 ms, the negative ones included
+        assert!(millis.iter().filter(|millis| **millis == 0).count() > 300);
+    }
+
+    #[test]
+    fn exponential_samples_average_the_mean() {
+        let millis = sample_millis(&ThinkTime::Exponential { mean: 100 }, 10000);
+        let average = millis.iter().sum::<u128>() as f64 / millis.len() as f64;
+        assert!((90.0..110.0).contains(&average), "{}", average);
+    }
+
+    #[test]
+    fn pacing_waits_for_the_rest_of_the_iteration() {
+        let pacing = ThinkTime::Pacing(1000);
+        assert_eq!(
+            pacing.get_pacing_remainder(Duration::from_millis(300)),
+            Some(Duration::from_millis(700))
+        );
+        assert_eq!(
+            pacing.get_pacing_remainder(Duration::from_millis(1500)),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            ThinkTime::Constant(1000).get_pacing_remainder(Duration::ZERO),
+            None
+        );
+    }
+
+    #[test]
+    fn uniform_think_times_need_an_ordered_range() {
+        assert!(ThinkTime::Uniform { min: 20, max: 10 }.validate().is_err());
+        assert!(ThinkTime::Uniform { min: 10, max: 20 }.validate().is_ok());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::Results;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// response times are given in milliseconds, rates between 0 and 1
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Metric {
+    AverageResponseTime,
+    MedianResponseTime,
+    Percentile90ResponseTime,
+    Percentile95ResponseTime,
+    Percentile99ResponseTime,
+    Percentile999ResponseTime,
+    MaxResponseTime,
+    FailureRate,
+    RequestsPerSecond,
+    FailedRequestsPerSecond,
+}
+
+impl Metric {
+    pub fn get_value(&self, results: &Results) -> f64 {
+        let as_millis = |response_time: u64| response_time as f64 / 1000.0;
+        match self {
+            Metric::AverageResponseTime => as_millis(results.average_response_time),
+            Metric::MedianResponseTime => as_millis(results.median_response_time),
+            Metric::Percentile90ResponseTime => as_millis(results.percentile_90_response_time),
+            Metric::Percentile95ResponseTime => as_millis(results.percentile_95_response_time),
+            Metric::Percentile99ResponseTime => as_millis(results.percentile_99_response_time),
+            Metric::Percentile999ResponseTime => as_millis(results.percentile_999_response_time),
+            Metric::MaxResponseTime => as_millis(results.max_response_time.unwrap_or(0)),
+            Metric::FailureRate => {
+                if results.total_requests == 0 {
+                    0.0
+                } else {
+                    results.total_failed_requests as f64 / results.total_requests as f64
+                }
+            }
+            Metric::RequestsPerSecond => results.requests_per_second,
+            Metric::FailedRequestsPerSecond => results.failed_requests_per_second,
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::AverageResponseTime => write!(f, "avg response time (ms)"),
+            Metric::MedianResponseTime => write!(f, "median response time (ms)"),
+            Metric::Percentile90ResponseTime => write!(f, "p90 response time (ms)"),
+            Metric::Percentile95ResponseTime => write!(f, "p95 response time (ms)"),
+            Metric::Percentile99ResponseTime => write!(f, "p99 response time (ms)"),
+            Metric::Percentile999ResponseTime => write!(f, "p99.9 response time (ms)"),
+            Metric::MaxResponseTime => write!(f, "max response time (ms)"),
+            Metric::FailureRate => write!(f, "failure rate"),
+            Metric::RequestsPerSecond => write!(f, "requests per second"),
+            Metric::FailedRequestsPerSecond => write!(f, "failed requests per second"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Comparison {
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+impl Comparison {
+    pub fn compare(&self, actual: f64, expected: f64) -> bool {
+        match self {
+            Comparison::LessThan => actual < expected,
+            Comparison::LessThanOrEqual => actual <= expected,
+            Comparison::GreaterThan => actual > expected,
+            Comparison::GreaterThanOrEqual => actual >= expected,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comparison::LessThan => write!(f, "<"),
+            Comparison::LessThanOrEqual => write!(f, "<="),
+            Comparison::GreaterThan => write!(f, ">"),
+            Comparison::GreaterThanOrEqual => write!(f, ">="),
+        }
+    }
+}
+
+/// A condition the results must meet for the test to pass, e.g. p95 of `POST /login` < 300ms.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Threshold {
+    pub metric: Metric,
+    pub comparison: Comparison,
+    pub value: f64,
+    pub endpoint: Option<String>, //ENDPOINT ID ("GET /login"), SCENARIO NAME, STEP ID OR USER CLASS, NONE FOR THE AGGREGATED RESULTS
+    pub abort_on_fail: bool,
+}
+
+impl Threshold {
+    pub fn new(metric: Metric, comparison: Comparison, value: f64) -> Threshold {
+        Threshold {
+            metric,
+            comparison,
+            value,
+            endpoint: None,
+            abort_on_fail: false,
+        }
+    }
+
+    pub fn set_endpoint(&mut self, endpoint: Option<String>) {
+        self.endpoint = endpoint;
+    }
+
+    pub fn set_abort_on_fail(&mut self, abort_on_fail: bool) {
+        self.abort_on_fail = abort_on_fail;
+    }
+
+    pub fn get_endpoint(&self) -> Option<&String> {
+        self.endpoint.as_ref()
+    }
+
+    pub fn get_abort_on_fail(&self) -> bool {
+        self.abort_on_fail
+    }
+
+    // returns the breach if the results do not meet the threshold
+    pub fn evaluate(&self, results: &Results) -> Option<ThresholdBreach> {
+        let actual = self.metric.get_value(results);
+        if self.comparison.compare(actual, self.value) {
+            None
+        } else {
+            Some(ThresholdBreach {
+                threshold: self.clone(),
+                actual,
+            })
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let endpoint = match self.endpoint {
+            Some(ref endpoint) => endpoint.as_str(),
+            None => "AGR",
+        };
+        write!(
+            f,
+            "{} of [{}] {} {}",
+            self.metric, endpoint, self.comparison, self.value
+        )
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ThresholdBreach {
+    pub threshold: Threshold,
+    pub actual: f64,
+}
+
+impl fmt::Display for ThresholdBreach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (actual {:.3})", self.threshold, self.actual)
+    }
+}
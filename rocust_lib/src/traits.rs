@@ -1,4 +1,4 @@
-use crate::{Results, Status};
+use crate::{errors::ErrorKind, Results, Status};
 use async_trait::async_trait;
 use parking_lot::RwLock;
 use serde::{de::DeserializeOwned, Serialize};
@@ -6,12 +6,15 @@ use std::{error::Error, sync::Arc, time::Duration};
 use tokio::fs;
 
 pub trait HasResults {
-    fn add_response_time(&self, response_time: u32);
-    fn add_failed(&self);
-    fn add_connection_error(&self);
+    fn add_response_time(&self, response_time: u64);
+    fn add_failed(&self, status_code: u16);
+    fn add_failed_check(&self, name: &str, reason: &str);
+    fn add_connection_error(&self, kind: ErrorKind, message: &str);
     fn set_requests_per_second(&self, requests_per_second: f64);
     fn calculate_requests_per_second(&self, elapsed: &Duration);
     fn calculate_failed_requests_per_second(&self, elapsed: &Duration);
+    fn calculate_current_requests_per_second(&self, elapsed: &Duration, window: &Duration);
+    fn calculate_percentiles(&self);
     fn get_results(&self) -> Arc<RwLock<Results>>;
     fn clone_results(&self) -> Results;
 }
@@ -42,11 +45,13 @@ where
         Ok(res)
     }
 
+    #[allow(clippy::wrong_self_convention)]
     fn into_json(&self) -> Result<String, Box<dyn Error>> {
         let json = serde_json::to_string(self)?;
         Ok(json)
     }
 
+    #[allow(clippy::wrong_self_convention)]
     async fn into_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
         let json = self.into_json()?;
         fs::write(path, json).await?;
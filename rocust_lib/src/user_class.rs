@@ -0,0 +1,398 @@
+use crate::{
+    endpoint, errors::ErrorKind, test::user::UserBehaviour, think_time::ThinkTime, EndPoint,
+    HasResults, Results, Scenario,
+};
+use parking_lot::RwLock;
+use serde::{
+    de::{MapAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{collections::HashMap, error::Error, fmt, sync::Arc, time::Duration};
+
+pub const DEFAULT_USER_CLASS_WEIGHT: u32 = 1;
+
+/// A kind of user of a test, like browsers, buyers or admins, with its own endpoints or scenarios.
+/// The users of a test are split between its classes by weight. The results of a class are the results of all its users.
+#[derive(Clone, Debug)]
+pub struct UserClass {
+    pub name: String,
+    pub weight: u32, //RELATIVE TO THE OTHER CLASSES OF THE TEST
+    pub endpoints: Arc<Vec<EndPoint>>,
+    pub scenarios: Arc<Vec<Scenario>>,
+    pub think_time: Option<ThinkTime>, //THE THINK TIME OF THE TEST IF NONE
+    pub headers: Option<HashMap<String, String>>, //ADDED TO THE GLOBAL HEADERS, REPLACING GLOBAL HEADERS WITH THE SAME NAME
+    pub results: Arc<RwLock<Results>>,
+}
+
+impl UserClass {
+    pub fn new(name: String, endpoints: Vec<EndPoint>, scenarios: Vec<Scenario>) -> UserClass {
+        UserClass {
+            name,
+            weight: DEFAULT_USER_CLASS_WEIGHT,
+            endpoints: Arc::new(endpoints),
+            scenarios: Arc::new(scenarios),
+            think_time: None,
+            headers: None,
+            results: Arc::new(RwLock::new(Results::new())),
+        }
+    }
+
+    pub fn set_weight(&mut self, weight: u32) {
+        self.weight = weight;
+    }
+
+    pub fn set_think_time(&mut self, think_time: Option<ThinkTime>) {
+        self.think_time = think_time;
+    }
+
+    pub fn set_behaviour(&mut self, user_behaviour: &UserBehaviour) {
+        self.think_time = Some(user_behaviour.get_think_time());
+    }
+
+    pub fn set_headers(&mut self, headers: Option<HashMap<String, String>>) {
+        self.headers = headers;
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn get_endpoints(&self) -> &Arc<Vec<EndPoint>> {
+        &self.endpoints
+    }
+
+    pub fn get_scenarios(&self) -> &Arc<Vec<Scenario>> {
+        &self.scenarios
+    }
+
+    pub fn get_think_time(&self) -> &Option<ThinkTime> {
+        &self.think_time
+    }
+
+    pub fn get_headers(&self) -> &Option<HashMap<String, String>> {
+        &self.headers
+    }
+
+    pub fn get_results(&self) -> &Arc<RwLock<Results>> {
+        &self.results
+    }
+
+    // the endpoints and the endpoints of all request steps of the class
+    pub fn get_all_endpoints(&self) -> impl Iterator<Item = &EndPoint> {
+        self.endpoints.iter().chain(
+            self.scenarios
+                .iter()
+                .flat_map(|scenario| scenario.get_requests()),
+        )
+    }
+
+    pub fn get_total_weight(&self) -> u64 {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.get_weight() as u64)
+            .sum()
+    }
+
+    // the share of the requests of the class the endpoint should get according to its weight
+    pub fn get_expected_mix(&self, endpoint: &EndPoint) -> f64 {
+        let total_weight = self.get_total_weight();
+        if total_weight == 0 {
+            return 0.0;
+        }
+        endpoint.get_weight() as f64 / total_weight as f64
+    }
+
+    // the share of the requests of the endpoints of the class the endpoint actually got
+    pub fn get_actual_mix(&self, endpoint: &EndPoint) -> f64 {
+        endpoint::get_actual_mix(endpoint, self.endpoints.iter())
+    }
+
+    pub fn merge_headers(
+        &self,
+        global_headers: &Option<HashMap<String, String>>,
+    ) -> Option<HashMap<String, String>> {
+        match (global_headers, &self.headers) {
+            (None, None) => None,
+            (global_headers, headers) => {
+                let mut merged = global_headers.clone().unwrap_or_default();
+                merged.extend(headers.clone().unwrap_or_default());
+                Some(merged)
+            }
+        }
+    }
+
+    // endpoints, scenarios and steps of different classes may share urls and names,
+    // so their results are sent to the master under ids prefixed with the name of the class
+    pub fn get_endpoint_id(&self, endpoint: &EndPoint) -> String {
+        format!("{}:{}", self.name, endpoint.get_id())
+    }
+
+    pub fn get_scenario_id(&self, scenario: &Scenario) -> String {
+        format!("{}:{}", self.name, scenario.get_name())
+    }
+
+    pub fn get_step_id(&self, scenario: &Scenario, index: usize) -> String {
+        format!("{}:{}", self.name, scenario.get_step_id(index))
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        endpoint::validate_unique_ids(self.endpoints.iter())?;
+        if self.scenarios.is_empty() {
+            if self.endpoints.is_empty() {
+                return Err(format!(
+                    "user class [{}] has no endpoints and no scenarios",
+                    self.name
+                )
+                .into());
+            }
+            if self.get_total_weight() == 0 {
+                return Err(format!(
+                    "all endpoints of user class [{}] have a weight of 0",
+                    self.name
+                )
+                .into());
+            }
+        } else if self
+            .scenarios
+            .iter()
+            .all(|scenario| scenario.get_weight() == 0)
+        {
+            return Err(format!(
+                "all scenarios of user class [{}] have a weight of 0",
+                self.name
+            )
+            .into());
+        }
+        if let Some(ref think_time) = self.think_time {
+            think_time.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for UserClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "UserClass [{}] | Weight [{}] | Endpoints [{}] | Scenarios [{}] | Results [{}]",
+            self.name,
+            self.weight,
+            self.endpoints.len(),
+            self.scenarios.len(),
+            self.results.read()
+        )
+    }
+}
+
+impl HasResults for UserClass {
+    fn add_response_time(&self, response_time: u64) {
+        self.results.write().add_response_time(response_time);
+    }
+
+    fn add_failed(&self, status_code: u16) {
+        self.results.write().add_failed(status_code);
+    }
+
+    fn add_failed_check(&self, name: &str, reason: &str) {
+        self.results.write().add_failed_check(name, reason);
+    }
+
+    fn add_connection_error(&self, kind: ErrorKind, message: &str) {
+        self.results.write().add_connection_error(kind, message);
+    }
+
+    fn set_requests_per_second(&self, requests_per_second: f64) {
+        self.results
+            .write()
+            .set_requests_per_second(requests_per_second);
+    }
+
+    fn calculate_requests_per_second(&self, elapsed: &Duration) {
+        self.results.write().calculate_requests_per_second(elapsed);
+        for endpoint in self.endpoints.iter() {
+            endpoint.calculate_requests_per_second(elapsed);
+        }
+        for scenario in self.scenarios.iter() {
+            scenario.calculate_requests_per_second(elapsed);
+        }
+    }
+
+    fn calculate_failed_requests_per_second(&self, elapsed: &Duration) {
+        self.results
+            .write()
+            .calculate_failed_requests_per_second(elapsed);
+        for endpoint in self.endpoints.iter() {
+            endpoint.calculate_failed_requests_per_second(elapsed);
+        }
+        for scenario in self.scenarios.iter() {
+            scenario.calculate_failed_requests_per_second(elapsed);
+        }
+    }
+
+    fn calculate_current_requests_per_second(&self, elapsed: &Duration, window: &Duration) {
+        self.results
+            .write()
+            .calculate_current_requests_per_second(elapsed, window);
+        for endpoint in self.endpoints.iter() {
+            endpoint.calculate_current_requests_per_second(elapsed, window);
+        }
+        for scenario in self.scenarios.iter() {
+            scenario.calculate_current_requests_per_second(elapsed, window);
+        }
+    }
+
+    fn calculate_percentiles(&self) {
+        self.results.write().calculate_percentiles();
+        for endpoint in self.endpoints.iter() {
+            endpoint.calculate_percentiles();
+        }
+        for scenario in self.scenarios.iter() {
+            scenario.calculate_percentiles();
+        }
+    }
+
+    fn get_results(&self) -> Arc<RwLock<Results>> {
+        self.results.clone()
+    }
+
+    fn clone_results(&self) -> Results {
+        self.results.read().clone()
+    }
+}
+
+impl Serialize for UserClass {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("UserClass", 7)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("weight", &self.weight)?;
+        state.serialize_field("endpoints", &*self.endpoints)?;
+        state.serialize_field("scenarios", &*self.scenarios)?;
+        state.serialize_field("think_time", &self.think_time)?;
+        state.serialize_field("headers", &self.headers)?;
+        state.serialize_field("results", &*self.results.read())?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for UserClass {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct UserClassVisitor;
+
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "snake_case")]
+        enum Field {
+            Name,
+            Weight,
+            Endpoints,
+            Scenarios,
+            ThinkTime,
+            Headers,
+            Results,
+        }
+        impl<'de> Visitor<'de> for UserClassVisitor {
+            type Value = UserClass;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("struct UserClass")
+            }
+
+            fn visit_map<V>(self, mut map: V) -> Result<UserClass, V::Error>
+            where
+                V: MapAccess<'de>,
+            {
+                let mut name: Option<String> = None;
+                let mut weight: Option<u32> = None;
+                let mut endpoints: Option<Vec<EndPoint>> = None;
+                let mut scenarios: Option<Vec<Scenario>> = None;
+                let mut think_time: Option<Option<ThinkTime>> = None;
+                let mut headers: Option<Option<HashMap<String, String>>> = None;
+                let mut results: Option<Results> = None;
+
+                while let Some(key) = map.next_key()? {
+                    match key {
+                        Field::Name => {
+                            if name.is_some() {
+                                return Err(serde::de::Error::duplicate_field("name"));
+                            }
+                            name = Some(map.next_value()?);
+                        }
+                        Field::Weight => {
+                            if weight.is_some() {
+                                return Err(serde::de::Error::duplicate_field("weight"));
+                            }
+                            weight = Some(map.next_value()?);
+                        }
+                        Field::Endpoints => {
+                            if endpoints.is_some() {
+                                return Err(serde::de::Error::duplicate_field("endpoints"));
+                            }
+                            endpoints = Some(map.next_value()?);
+                        }
+                        Field::Scenarios => {
+                            if scenarios.is_some() {
+                                return Err(serde::de::Error::duplicate_field("scenarios"));
+                            }
+                            scenarios = Some(map.next_value()?);
+                        }
+                        Field::ThinkTime => {
+                            if think_time.is_some() {
+                                return Err(serde::de::Error::duplicate_field("think_time"));
+                            }
+                            think_time = Some(map.next_value()?);
+                        }
+                        Field::Headers => {
+                            if headers.is_some() {
+                                return Err(serde::de::Error::duplicate_field("headers"));
+                            }
+                            headers = Some(map.next_value()?);
+                        }
+                        Field::Results => {
+                            if results.is_some() {
+                                return Err(serde::de::Error::duplicate_field("results"));
+                            }
+                            results = Some(map.next_value()?);
+                        }
+                    }
+                }
+                let name = name.ok_or_else(|| serde::de::Error::missing_field("name"))?;
+                let weight = weight.unwrap_or(DEFAULT_USER_CLASS_WEIGHT);
+                let endpoints = endpoints.unwrap_or_default();
+                let scenarios = scenarios.unwrap_or_default();
+                let think_time = think_time.unwrap_or_default();
+                let headers = headers.unwrap_or_default();
+                let results = results.unwrap_or_default();
+
+                Ok(UserClass {
+                    name,
+                    weight,
+                    endpoints: Arc::new(endpoints),
+                    scenarios: Arc::new(scenarios),
+                    think_time,
+                    headers,
+                    results: Arc::new(RwLock::new(results)),
+                })
+            }
+        }
+        const FIELDS: &[&str] = &[
+            "name",
+            "weight",
+            "endpoints",
+            "scenarios",
+            "think_time",
+            "headers",
+            "results",
+        ];
+        deserializer.deserialize_struct("UserClass", FIELDS, UserClassVisitor)
+    }
+}
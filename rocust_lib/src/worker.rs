@@ -56,7 +56,18 @@ impl Worker {
         if let Some(ref test) = *self.test.read() {
             let agg_sent_results = test.clone_results().create_sent_results();
             let endpoints_sent_results = test.create_endpoints_sent_results();
-            let results_websocket_message = ResultsWebsocketMessage::new(agg_sent_results, endpoints_sent_results);
+            let scenarios_sent_results = test.create_scenarios_sent_results();
+            let steps_sent_results = test.create_steps_sent_results();
+            let user_classes_sent_results = test.create_user_classes_sent_results();
+            let active_users = test.get_active_users_count();
+            let results_websocket_message = ResultsWebsocketMessage::new(
+                agg_sent_results,
+                endpoints_sent_results,
+                scenarios_sent_results,
+                steps_sent_results,
+                user_classes_sent_results,
+                active_users,
+            );
             Some(results_websocket_message)
         } else {
             None
@@ -76,9 +87,9 @@ impl Worker {
         let origin = url.origin();
         match origin {
             url::Origin::Tuple(scheme, _host, _port) => {
-                if scheme == String::from("http") {
+                if scheme == "http" {
                     ws_scheme = "ws";
-                } else if scheme == String::from("https") {
+                } else if scheme == "https" {
                     ws_scheme = "wss";
                 } else {
                     let message = format!("Unknown scheme: {}", scheme);
@@ -140,6 +151,8 @@ impl Worker {
                                             self.print_stats_to_console,
                                         );
                                         test.set_run_time(None);
+                                        // the master evaluates the thresholds on the combined results
+                                        test.set_thresholds(Vec::new());
                                         self.logger.log_buffered(
                                             LogType::Info,
                                             &format!(
@@ -160,6 +173,14 @@ impl Worker {
                                         self.stop();
                                     }
 
+                                    ControlWebSocketMessage::RampDown(ramp_down) => {
+                                        self.logger.log_buffered(
+                                            LogType::Info,
+                                            &format!("Ramping down in {} seconds", ramp_down),
+                                        );
+                                        self.ramp_down_test(ramp_down);
+                                    }
+
                                     ControlWebSocketMessage::Finish => {
                                         self.logger.log_buffered(LogType::Info, "Finishing test");
                                         self.finish();
@@ -225,6 +246,16 @@ impl Worker {
         }
     }
 
+    // the users are stopped in the background, the master sends finish at the end of the ramp down
+    pub fn ramp_down_test(&self, ramp_down: u64) {
+        let test = self.test.read().clone();
+        if let Some(test) = test {
+            tokio::spawn(async move {
+                test.ramp_down(ramp_down).await;
+            });
+        }
+    }
+
     pub fn finish_test(&self) {
         let guard = self.test.read();
         if let Some(test) = guard.as_ref() {
@@ -279,7 +310,7 @@ impl Worker {
         loop {
             if let Some(ref tx) = tx {
                 let test = self.test.read().clone();
-                if let Some(_) = test {
+                if test.is_some() {
                     let results_websocket_message = self.create_results_websocket_message();
                     if let Some(results_websocket_message) = results_websocket_message{
                         let message = ControlWebSocketMessage::Update(results_websocket_message);
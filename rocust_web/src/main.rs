@@ -43,7 +43,7 @@ fn add_test(Path(id): Path<String>, tests: Data<&TestCollection>) -> String {
 
     tests.insert(id.clone(), new_test);
 
-    format!("Ok")
+    "Ok".to_string()
 }
 
 #[handler]
@@ -54,9 +54,9 @@ fn start_test(Path(id): Path<String>, tests: Data<&TestCollection>) -> String {
         tokio::spawn(async move {
             test.run().await;
         });
-        format!("Ok")
+        "Ok".to_string()
     } else {
-        format!("NotFound")
+        "NotFound".to_string()
     }
 }
 
@@ -65,9 +65,9 @@ fn stop_test(Path(id): Path<String>, tests: Data<&TestCollection>) -> String {
     let tests = tests.read();
     if let Some(test) = tests.get(&id) {
         test.stop();
-        format!("Ok")
+        "Ok".to_string()
     } else {
-        format!("NotFound")
+        "NotFound".to_string()
     }
 }
 